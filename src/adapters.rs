@@ -0,0 +1,925 @@
+//! Converts results from load testing tools other than wrk2 into histograms.
+
+use std::collections::HashMap;
+
+use hdrhistogram::serialization::Deserializer;
+use hdrhistogram::Histogram;
+use serde_json::Value;
+
+use crate::hgrm::{HGRMs, HGRM};
+use crate::input::InputOptions;
+use crate::units::{parse_duration, MICROS_PER_MILLI};
+
+pub const DEFAULT_SIGNIFICANT_DIGITS: u32 = 3;
+
+/// Metric k6 records request latencies under.
+const K6_DURATION: &str = "http_req_duration";
+/// Samples assumed when a summary doesn't include a request count.
+const DEFAULT_SUMMARY_COUNT: u64 = 1000;
+const MICROS_PER_SECOND: f64 = 1_000_000.0;
+const NANOS_PER_MICRO: f64 = 1000.0;
+
+/// Records raw samples, in microseconds, into an HdrHistogram with a
+/// relative precision of `significant_digits` decimal digits. Samples are
+/// recorded in nanoseconds, so sub-microsecond latencies keep their precision.
+#[derive(Clone, Debug)]
+pub struct Recorder(Histogram<u64>);
+
+impl Recorder {
+    pub fn new(significant_digits: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        if !(1..=5).contains(&significant_digits) {
+            return Err(format!(
+                "Significant digits `{}` must be between 1 and 5",
+                significant_digits
+            )
+            .into());
+        }
+
+        Ok(Self(Histogram::new(significant_digits as u8)?))
+    }
+
+    /// Records `count` samples of a value in microseconds, rounded to the
+    /// nearest nanosecond. Negative and non-finite values are ignored.
+    pub fn record_n(&mut self, value: f64, count: u64) {
+        if !value.is_finite() || value < 0.0 || count == 0 {
+            return;
+        }
+
+        let value = (value * NANOS_PER_MICRO).round() as u64;
+
+        // The histogram resizes to fit, up to centuries of nanoseconds
+        if self.0.record_n(value, count).is_err() {
+            self.0.saturating_record_n(value, count);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_hgrm(&self) -> HGRM {
+        from_histogram(&self.0, NANOS_PER_MICRO)
+    }
+}
+
+/// Recorders for named series, kept in the order they were first seen.
+#[derive(Debug)]
+pub struct Recorders {
+    empty: Recorder,
+    names: Vec<String>,
+    recorders: HashMap<String, Recorder>,
+}
+
+impl Recorders {
+    pub fn new(significant_digits: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            empty: Recorder::new(significant_digits)?,
+            names: vec![],
+            recorders: HashMap::new(),
+        })
+    }
+
+    pub fn record(&mut self, name: &str, value: f64) {
+        if !self.recorders.contains_key(name) {
+            self.names.push(name.to_string());
+            self.recorders.insert(name.to_string(), self.empty.clone());
+        }
+
+        if let Some(recorder) = self.recorders.get_mut(name) {
+            recorder.record_n(value, 1);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn to_hgrms(&self) -> HGRMs {
+        HGRMs::new(
+            self.names
+                .iter()
+                .map(|name| self.recorders[name].to_hgrm().set_name(Some(name)))
+                .collect(),
+        )
+    }
+}
+
+/// Approximates a histogram from a few percentiles (0.0 - 1.0) and their
+/// values, by spreading `count` samples between them.
+fn from_percentiles(mut points: Vec<(f64, f64)>, count: u64) -> HGRM {
+    points.sort_by(|(p1, _), (p2, _)| p1.total_cmp(p2));
+
+    let mut cumulative = 0;
+    let buckets: Vec<(f64, u64)> = points
+        .iter()
+        .map(|(percentile, value)| {
+            let to = ((percentile * count as f64).round() as u64).max(1);
+            let bucket = (*value, to.saturating_sub(cumulative));
+            cumulative = cumulative.max(to);
+
+            bucket
+        })
+        .collect();
+
+    HGRM::from_buckets(&buckets)
+}
+
+/// Parses one raw sample, in `unit` unless it has its own, e.g. `12.5ms`.
+fn sample(s: &str, unit: f64) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(value) => Ok(value * unit),
+        Err(_) => parse_duration(s),
+    }
+}
+
+/// Parses raw samples, one per line, skipping blank lines and `#` comments.
+/// A first line which isn't a sample is taken as a header naming the series.
+pub fn samples(data: &str, options: &InputOptions) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let mut recorder = Recorder::new(options.significant_digits)?;
+    let mut name = None;
+    let lines = data
+        .lines()
+        .enumerate()
+        .map(|(row, line)| (row, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    for (idx, (row, line)) in lines.enumerate() {
+        match sample(line, options.sample_unit()) {
+            Ok(value) => recorder.record_n(value, 1),
+            Err(_) if idx == 0 => name = Some(line),
+            Err(e) => return Err(format!("{} on line {}", e, row + 1).into()),
+        }
+    }
+
+    if recorder.is_empty() {
+        return Err("No samples found".into());
+    }
+
+    Ok(HGRMs::new(vec![recorder.to_hgrm().set_name(name)]))
+}
+
+/// Parses raw samples from a CSV column, chosen by header name or 1-based
+/// position.
+pub fn csv_samples(
+    data: &str,
+    column: &str,
+    options: &InputOptions,
+) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let mut records = reader.records();
+    let headers = records.next().ok_or("No CSV rows found")??;
+
+    let (idx, name) = match headers.iter().position(|header| header.trim() == column) {
+        Some(idx) => (idx, Some(column.to_string())),
+        None => match column.parse::<usize>() {
+            Ok(position) if position > 0 => (position - 1, None),
+            _ => return Err(format!("Missing `{}` column", column).into()),
+        },
+    };
+
+    let mut recorder = Recorder::new(options.significant_digits)?;
+    let mut name = name;
+    let rows = std::iter::once(Ok(headers)).chain(records);
+
+    for (row, record) in rows.enumerate() {
+        let record = record?;
+        let field = record
+            .get(idx)
+            .ok_or_else(|| format!("Missing column {} on row {}", idx + 1, row + 1))?;
+
+        match sample(field, options.sample_unit()) {
+            Ok(value) => recorder.record_n(value, 1),
+            // A header row, naming the series when chosen by position
+            Err(_) if row == 0 => name = name.or_else(|| Some(field.trim().to_string())),
+            Err(e) => return Err(format!("{} on row {}", e, row + 1).into()),
+        }
+    }
+
+    if recorder.is_empty() {
+        return Err(format!("No samples found in column `{}`", column).into());
+    }
+
+    Ok(HGRMs::new(vec![recorder
+        .to_hgrm()
+        .set_name(name.as_deref())]))
+}
+
+/// Parses a k6 summary, from `--summary-export` or `handleSummary`, or the
+/// metric stream written by `--out json`.
+pub fn k6(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    match serde_json::from_str::<Value>(data) {
+        Ok(summary) if summary.get("metrics").is_some() => k6_summary(&summary),
+        _ => k6_stream(data, digits),
+    }
+}
+
+/// Records every `http_req_duration` point, one series per `name` tag.
+fn k6_stream(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let mut recorders = Recorders::new(digits)?;
+
+    for (row, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let point: Value = serde_json::from_str(line)
+            .map_err(|e| format!("Invalid k6 JSON on line {}: {}", row + 1, e))?;
+
+        if point["type"] != "Point" || point["metric"] != K6_DURATION {
+            continue;
+        }
+
+        let value = point["data"]["value"]
+            .as_f64()
+            .ok_or_else(|| format!("Missing value on line {}", row + 1))?;
+        let name = point["data"]["tags"]["name"]
+            .as_str()
+            .unwrap_or(K6_DURATION);
+
+        recorders.record(name, value * MICROS_PER_MILLI);
+    }
+
+    if recorders.is_empty() {
+        return Err(format!("No {} points found", K6_DURATION).into());
+    }
+
+    Ok(recorders.to_hgrms())
+}
+
+/// A summary only holds a few percentiles, so each series is approximated
+/// by spreading the request count between them.
+fn k6_summary(summary: &Value) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let metrics = summary["metrics"]
+        .as_object()
+        .ok_or("Expected an object of k6 metrics")?;
+    // `handleSummary` nests values, `--summary-export` doesn't
+    let values = |metric: &Value| metric.get("values").unwrap_or(metric).clone();
+    let count = metrics
+        .get("http_reqs")
+        .and_then(|reqs| values(reqs)["count"].as_u64())
+        .unwrap_or(DEFAULT_SUMMARY_COUNT);
+
+    let hgrms: Vec<HGRM> = metrics
+        .iter()
+        .filter(|(name, _)| name.starts_with(K6_DURATION))
+        .map(|(name, metric)| {
+            let points = values(metric)
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter_map(|(stat, value)| {
+                    Some((k6_percentile(stat)?, value.as_f64()? * MICROS_PER_MILLI))
+                })
+                .collect();
+
+            from_percentiles(points, count).set_name(Some(name))
+        })
+        .collect();
+
+    if hgrms.is_empty() {
+        return Err(format!("No {} metrics found", K6_DURATION).into());
+    }
+
+    Ok(HGRMs::new(hgrms))
+}
+
+/// Percentile (0.0 - 1.0) of a k6 trend statistic such as `med` or `p(99.9)`.
+fn k6_percentile(stat: &str) -> Option<f64> {
+    match stat {
+        "min" => Some(0.0),
+        "med" => Some(0.5),
+        "max" => Some(1.0),
+        _ => {
+            let percentile: f64 = stat.strip_prefix("p(")?.strip_suffix(')')?.parse().ok()?;
+
+            Some(percentile / 100.0)
+        }
+    }
+}
+
+/// Parses a JMeter JTL results file in CSV format, one series per label.
+pub fn jmeter(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| format!("Missing `{}` column", name))
+    };
+    let elapsed = column("elapsed")?;
+    let label = column("label").ok();
+
+    let mut recorders = Recorders::new(digits)?;
+
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        let value: f64 = record
+            .get(elapsed)
+            .and_then(|elapsed| elapsed.trim().parse().ok())
+            .ok_or_else(|| format!("Invalid elapsed time on row {}", row + 2))?;
+        let name = label
+            .and_then(|label| record.get(label))
+            .unwrap_or("elapsed");
+
+        recorders.record(name, value * MICROS_PER_MILLI);
+    }
+
+    if recorders.is_empty() {
+        return Err("No JMeter samples found".into());
+    }
+
+    Ok(recorders.to_hgrms())
+}
+
+/// Parses a Gatling `simulation.log`, one series per request name. The
+/// columns before the request name differ between Gatling versions, so
+/// requests are read from the end of the line.
+pub fn gatling(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let mut recorders = Recorders::new(digits)?;
+
+    for (row, line) in data.lines().enumerate() {
+        let fields: Vec<&str> = line.split('\t').collect();
+
+        if fields.first() != Some(&"REQUEST") {
+            continue;
+        }
+
+        // ... <name> <start> <end> <OK|KO> <message>
+        let status = fields
+            .iter()
+            .rposition(|field| *field == "OK" || *field == "KO")
+            .filter(|status| *status >= 4)
+            .ok_or_else(|| format!("Invalid Gatling request on line {}", row + 1))?;
+        let timestamp = |idx: usize| {
+            fields[idx]
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid timestamp on line {}", row + 1))
+        };
+        let (start, end) = (timestamp(status - 2)?, timestamp(status - 1)?);
+
+        recorders.record(fields[status - 3], (end - start) * MICROS_PER_MILLI);
+    }
+
+    if recorders.is_empty() {
+        return Err("No Gatling requests found".into());
+    }
+
+    Ok(recorders.to_hgrms())
+}
+
+/// Parses fortio's JSON output, recording each duration histogram bucket
+/// at its upper bound.
+pub fn fortio(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let result: Value = serde_json::from_str(data)?;
+    let buckets = result["DurationHistogram"]["Data"]
+        .as_array()
+        .ok_or("Missing fortio DurationHistogram")?;
+    let name = result["Labels"]
+        .as_str()
+        .filter(|labels| !labels.is_empty());
+
+    let mut recorder = Recorder::new(digits)?;
+    for bucket in buckets {
+        let end = bucket["End"].as_f64().ok_or("Missing fortio bucket End")?;
+        let count = bucket["Count"]
+            .as_u64()
+            .ok_or("Missing fortio bucket Count")?;
+
+        recorder.record_n(end * MICROS_PER_SECOND, count);
+    }
+
+    Ok(HGRMs::new(vec![recorder.to_hgrm().set_name(name)]))
+}
+
+/// Parses a ghz JSON report, from the latency of each request when details
+/// are included and its histogram otherwise.
+pub fn ghz(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let report: Value = serde_json::from_str(data)?;
+    let name = report["name"].as_str().filter(|name| !name.is_empty());
+    let mut recorder = Recorder::new(digits)?;
+
+    match report["details"]
+        .as_array()
+        .filter(|details| !details.is_empty())
+    {
+        Some(details) => {
+            for detail in details {
+                let latency = detail["latency"].as_f64().ok_or("Missing ghz latency")?;
+
+                recorder.record_n(latency / NANOS_PER_MICRO, 1);
+            }
+        }
+        None => {
+            let histogram = report["histogram"]
+                .as_array()
+                .ok_or("Missing ghz histogram")?;
+
+            for bucket in histogram {
+                let mark = bucket["mark"]
+                    .as_f64()
+                    .ok_or("Missing ghz histogram mark")?;
+                let count = bucket["count"]
+                    .as_u64()
+                    .ok_or("Missing ghz histogram count")?;
+
+                recorder.record_n(mark * MICROS_PER_SECOND, count);
+            }
+        }
+    }
+
+    Ok(HGRMs::new(vec![recorder.to_hgrm().set_name(name)]))
+}
+
+/// Parses vegeta's `report -type=json` summary, `report -type=hist` table or
+/// the JSON results written by `encode --to json`.
+pub fn vegeta(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    if data.trim_start().starts_with("Bucket") {
+        return vegeta_hist(data, digits);
+    }
+
+    match serde_json::from_str::<Value>(data) {
+        Ok(report) if report.get("latencies").is_some() => {
+            let latencies = &report["latencies"];
+            let count = report["requests"].as_u64().unwrap_or(DEFAULT_SUMMARY_COUNT);
+            let points = [
+                ("min", 0.0),
+                ("50th", 0.5),
+                ("90th", 0.9),
+                ("95th", 0.95),
+                ("99th", 0.99),
+                ("max", 1.0),
+            ]
+            .iter()
+            .filter_map(|(stat, percentile)| {
+                Some((*percentile, latencies[*stat].as_f64()? / NANOS_PER_MICRO))
+            })
+            .collect();
+
+            Ok(HGRMs::new(vec![from_percentiles(points, count)]))
+        }
+        _ => {
+            let mut recorder = Recorder::new(digits)?;
+
+            for (row, line) in data.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let result: Value = serde_json::from_str(line)
+                    .map_err(|e| format!("Invalid vegeta JSON on line {}: {}", row + 1, e))?;
+                let latency = result["latency"]
+                    .as_f64()
+                    .ok_or_else(|| format!("Missing latency on line {}", row + 1))?;
+
+                recorder.record_n(latency / NANOS_PER_MICRO, 1);
+            }
+
+            Ok(HGRMs::new(vec![recorder.to_hgrm()]))
+        }
+    }
+}
+
+/// Records each `[lower, upper]` bucket of the table at its upper bound, or
+/// its lower bound for the last, unbounded, bucket.
+fn vegeta_hist(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let mut recorder = Recorder::new(digits)?;
+
+    for (row, line) in data.lines().enumerate() {
+        let line = line.trim();
+        let Some((bounds, rest)) = line.strip_prefix('[').and_then(|l| l.split_once(']')) else {
+            continue;
+        };
+
+        let invalid = || format!("Invalid vegeta histogram bucket on line {}", row + 1);
+        let (lower, upper) = bounds.split_once(',').ok_or_else(invalid)?;
+        let count: u64 = rest
+            .split_whitespace()
+            .next()
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(invalid)?;
+        let value = match upper.trim() {
+            "+Inf" => lower,
+            upper => upper,
+        };
+
+        recorder.record_n(parse_duration(value.trim())?, count);
+    }
+
+    if recorder.is_empty() {
+        return Err("No vegeta histogram buckets found".into());
+    }
+
+    Ok(HGRMs::new(vec![recorder.to_hgrm()]))
+}
+
+/// Parses hey's `-o csv` output, recording the response time of each request.
+pub fn hey(data: &str, digits: u32) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let options = InputOptions {
+        significant_digits: digits,
+        column: Some("response-time".to_string()),
+        unit: Some(MICROS_PER_SECOND),
+        ..Default::default()
+    };
+
+    Ok(csv_samples(data, "response-time", &options)?
+        .into_iter()
+        .map(|hgrm| hgrm.set_name(None))
+        .collect())
+}
+
+/// Decodes base64 encoded HdrHistogram payloads, either a single one or an
+/// interval log. Intervals are added together per `Tag=`, which names them.
+/// Values are `unit` microseconds each.
+pub fn histf(data: &str, unit: f64) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let mut deserializer = Deserializer::new();
+    let mut histograms: Vec<(Option<&str>, Histogram<u64>)> = vec![];
+
+    for (row, line) in data.lines().enumerate() {
+        let Some(payload) = line
+            .split(',')
+            .map(str::trim)
+            .find(|field| field.starts_with("HISTF"))
+        else {
+            continue;
+        };
+        let tag = line.split(',').find_map(|field| field.strip_prefix("Tag="));
+
+        let bytes = base64::decode(payload)?;
+        let interval: Histogram<u64> = deserializer
+            .deserialize(&mut &bytes[..])
+            .map_err(|e| format!("{} on line {}", e, row + 1))?;
+
+        match histograms.iter_mut().find(|(name, _)| *name == tag) {
+            Some((_, histogram)) => histogram.add(&interval)?,
+            None => {
+                let mut histogram = interval;
+                histogram.auto(true);
+                histograms.push((tag, histogram));
+            }
+        }
+    }
+
+    if histograms.is_empty() {
+        return Err("No HdrHistogram payloads found".into());
+    }
+
+    Ok(HGRMs::new(
+        histograms
+            .iter()
+            .map(|(name, histogram)| from_histogram(histogram, 1.0 / unit).set_name(*name))
+            .collect(),
+    ))
+}
+
+/// Converts a histogram of values recorded `per_micro` to a microsecond,
+/// reported at the highest value equivalent to each bucket.
+fn from_histogram(histogram: &Histogram<u64>, per_micro: f64) -> HGRM {
+    let buckets: Vec<(f64, u64)> = histogram
+        .iter_recorded()
+        .map(|value| {
+            (
+                value.value_iterated_to() as f64 / per_micro,
+                value.count_at_value(),
+            )
+        })
+        .collect();
+    let sub_buckets = (2 * 10u64.pow(histogram.sigfig() as u32)).next_power_of_two();
+
+    HGRM::from_buckets(&buckets)
+        .set_buckets(histogram.buckets() as u64)
+        .set_sub_buckets(sub_buckets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::parse;
+    use hdrhistogram::serialization::{Serializer, V2DeflateSerializer};
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn summary(hgrms: &HGRMs) -> Vec<(String, u64, f64)> {
+        hgrms
+            .iter()
+            .map(|hgrm| {
+                (
+                    hgrm.name.clone().unwrap_or_default(),
+                    hgrm.total_count,
+                    hgrm.max,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_k6_stream() {
+        let data = r#"{"type":"Metric","data":{"name":"http_req_duration","type":"trend"},"metric":"http_req_duration"}
+{"type":"Point","data":{"time":"2022-08-01T10:00:00Z","value":12.5,"tags":{"name":"home"}},"metric":"http_req_duration"}
+{"type":"Point","data":{"time":"2022-08-01T10:00:01Z","value":1,"tags":{"name":"home"}},"metric":"http_reqs"}
+{"type":"Point","data":{"time":"2022-08-01T10:00:01Z","value":30.25,"tags":{"name":"login"}},"metric":"http_req_duration"}
+{"type":"Point","data":{"time":"2022-08-01T10:00:02Z","value":14,"tags":{"name":"home"}},"metric":"http_req_duration"}
+"#;
+
+        assert_eq!(
+            summary(&parse(data, &InputOptions::default()).unwrap()),
+            vec![
+                ("home".to_string(), 2, 14000.127),
+                ("login".to_string(), 1, 30261.247),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_k6_summary() {
+        let data = r#"{
+  "metrics": {
+    "http_reqs": {"type": "counter", "values": {"count": 200, "rate": 20}},
+    "http_req_duration": {
+      "type": "trend",
+      "values": {"avg": 14.2, "min": 2, "med": 10, "max": 120, "p(90)": 25, "p(95)": 40}
+    }
+  }
+}"#;
+
+        let hgrms = parse(data, &InputOptions::default()).unwrap();
+        let hgrm = &hgrms[0];
+
+        assert_eq!(hgrm.name.as_deref(), Some("http_req_duration"));
+        assert_eq!(hgrm.total_count, 200);
+        assert_eq!(hgrm.value_at_percentile(0.0), 2000.0);
+        assert_eq!(hgrm.value_at_percentile(0.5), 10000.0);
+        assert_eq!(hgrm.value_at_percentile(0.95), 40000.0);
+        assert_eq!(hgrm.max, 120000.0);
+    }
+
+    #[test]
+    fn test_jmeter() {
+        let data = "timeStamp,elapsed,label,responseCode,responseMessage,threadName,success\n\
+                    1659348000000,120,Home,200,OK,Thread 1-1,true\n\
+                    1659348000100,\"80\",\"Login, with password\",200,OK,Thread 1-2,true\n\
+                    1659348000200,100,Home,500,Error,Thread 1-1,false\n";
+
+        assert_eq!(
+            summary(&parse(data, &InputOptions::default()).unwrap()),
+            vec![
+                ("Home".to_string(), 2, 120061.951),
+                ("Login, with password".to_string(), 1, 80019.455),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gatling() {
+        // Gatling 3.0 - 3.4 logs a user id before the groups, later versions don't
+        let data = "RUN\tcomputerdatabase.BasicSimulation\tbasicsimulation\t1573559212358\t \t3.3.1\n\
+                    USER\tScenario Name\t1\tSTART\t1573559213187\t1573559213187\n\
+                    REQUEST\t1\t\trequest_1\t1573559213298\t1573559213452\tOK\t \n\
+                    REQUEST\t\trequest_2\t1573559213500\t1573559213550\tKO\tstatus.find.is(200) failed\n\
+                    REQUEST\t1\t\trequest_1\t1573559213600\t1573559213700\tOK\t \n";
+
+        assert_eq!(
+            summary(&parse(data, &InputOptions::default()).unwrap()),
+            vec![
+                ("request_1".to_string(), 2, 154009.599),
+                ("request_2".to_string(), 1, 50003.967),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recorder() {
+        let mut recorder = Recorder::new(3).unwrap();
+        // Sub-microsecond samples, e.g. in ns, keep their precision
+        for value in 1..=100 {
+            recorder.record_n(value as f64 * 0.01, 1);
+        }
+        recorder.record_n(-1.0, 1);
+        recorder.record_n(f64::NAN, 1);
+
+        let hgrm = recorder.to_hgrm();
+
+        assert_eq!(hgrm.total_count, 100);
+        assert_eq!(hgrm.max, 1.0);
+        assert_eq!(hgrm.value_at_percentile(0.5), 0.5);
+        assert_eq!(hgrm.value_at_percentile(0.99), 0.99);
+        assert_eq!(hgrm.sub_buckets, 2048);
+
+        assert!(Recorder::new(0).is_err());
+        assert!(Recorder::new(6).is_err());
+    }
+
+    #[test]
+    fn test_recorders_keep_order() {
+        let mut recorders = Recorders::new(3).unwrap();
+        recorders.record("b", 1.0);
+        recorders.record("a", 2.0);
+        recorders.record("b", 3.0);
+
+        assert_eq!(
+            summary(&recorders.to_hgrms()),
+            vec![("b".to_string(), 2, 3.001), ("a".to_string(), 1, 2.0)]
+        );
+    }
+
+    #[test]
+    fn test_unrecognised() {
+        assert!(parse("hello world", &InputOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_samples() {
+        let data = "# exported from tracing\nlatency\n1500\n\n2.5ms\n1500\n";
+        let options = InputOptions::default();
+
+        let hgrms = parse(data, &options).unwrap();
+        let hgrm = &hgrms[0];
+
+        assert_eq!(hgrm.name.as_deref(), Some("latency"));
+        assert_eq!(hgrm.total_count, 3);
+        assert_eq!(hgrm.value_at_percentile(0.5), 1500.159);
+        assert_eq!(hgrm.max, 2500.607);
+
+        assert!(parse("1\n2\nthree\n", &options).is_err());
+    }
+
+    #[test]
+    fn test_samples_unit_and_digits() {
+        let options = InputOptions {
+            significant_digits: 1,
+            unit: Some(MICROS_PER_MILLI),
+            ..Default::default()
+        };
+
+        let hgrms = parse("12.3\n45.6\n", &options).unwrap();
+
+        // One significant digit records 45.6ms in the bucket 44.040 - 46.137ms
+        assert_eq!(hgrms[0].max, 46137.343);
+        assert_eq!(hgrms[0].sub_buckets, 32);
+
+        let options = InputOptions {
+            unit: Some(0.001),
+            ..Default::default()
+        };
+
+        let hgrms = parse("250\n750\n", &options).unwrap();
+
+        assert_eq!(hgrms[0].value_at_percentile(0.5), 0.25);
+        assert_eq!(hgrms[0].max, 0.75);
+    }
+
+    #[test]
+    fn test_csv_samples() {
+        let data = "trace_id,duration_ms\nabc,12\n\"d,e\",30\n";
+        let by_name = InputOptions {
+            column: Some("duration_ms".to_string()),
+            unit: Some(MICROS_PER_MILLI),
+            ..Default::default()
+        };
+        let by_position = InputOptions {
+            column: Some("2".to_string()),
+            ..by_name.clone()
+        };
+
+        for options in [by_name, by_position] {
+            assert_eq!(
+                summary(&parse(data, &options).unwrap()),
+                vec![("duration_ms".to_string(), 2, 30015.487)]
+            );
+        }
+
+        let missing = InputOptions {
+            column: Some("latency".to_string()),
+            ..Default::default()
+        };
+        assert!(parse(data, &missing).is_err());
+    }
+
+    #[test]
+    fn test_fortio() {
+        let data = r#"{
+  "RunType": "HTTP",
+  "Labels": "checkout",
+  "DurationHistogram": {
+    "Count": 30,
+    "Data": [
+      {"Start": 0.001, "End": 0.002, "Percent": 33.3, "Count": 10},
+      {"Start": 0.002, "End": 0.003, "Percent": 100, "Count": 20}
+    ]
+  }
+}"#;
+
+        assert_eq!(
+            summary(&parse(data, &InputOptions::default()).unwrap()),
+            vec![("checkout".to_string(), 30, 3000.319)]
+        );
+    }
+
+    #[test]
+    fn test_ghz() {
+        let details = r#"{
+  "name": "greeter",
+  "count": 2,
+  "rps": 200,
+  "histogram": [{"mark": 0.002, "count": 2, "frequency": 1}],
+  "details": [
+    {"timestamp": "2022-08-01T10:00:00Z", "latency": 1500000, "error": "", "status": "OK"},
+    {"timestamp": "2022-08-01T10:00:00Z", "latency": 900000, "error": "", "status": "OK"}
+  ]
+}"#;
+        let histogram = r#"{
+  "name": "",
+  "count": 3,
+  "rps": 200,
+  "histogram": [{"mark": 0.001, "count": 1, "frequency": 0.33}, {"mark": 0.002, "count": 2, "frequency": 0.67}],
+  "latencyDistribution": [{"percentage": 50, "latency": 2000000}]
+}"#;
+
+        assert_eq!(
+            summary(&parse(details, &InputOptions::default()).unwrap()),
+            vec![("greeter".to_string(), 2, 1500.159)]
+        );
+        assert_eq!(
+            summary(&parse(histogram, &InputOptions::default()).unwrap()),
+            vec![(String::new(), 3, 2000.895)]
+        );
+    }
+
+    #[test]
+    fn test_vegeta() {
+        let report = r#"{"latencies":{"total":3000000000,"mean":3000000,"50th":2000000,"90th":5000000,"95th":6000000,"99th":9000000,"max":12000000,"min":1000000},"requests":1000}"#;
+        let results = "{\"attack\":\"\",\"seq\":0,\"code\":200,\"latency\":1200000}\n\
+                       {\"attack\":\"\",\"seq\":1,\"code\":200,\"latency\":800000}\n";
+        let hist = "Bucket           #     %       Histogram\n\
+                    [0s,     2ms]    3     75.00%  ########\n\
+                    [2ms,    4ms]    0     0.00%\n\
+                    [4ms,    +Inf]   1     25.00%  ##\n";
+
+        let hgrms = parse(report, &InputOptions::default()).unwrap();
+        assert_eq!(hgrms[0].total_count, 1000);
+        assert_eq!(hgrms[0].value_at_percentile(0.5), 2000.0);
+        assert_eq!(hgrms[0].value_at_percentile(0.99), 9000.0);
+
+        assert_eq!(
+            summary(&parse(results, &InputOptions::default()).unwrap()),
+            vec![(String::new(), 2, 1200.127)]
+        );
+        assert_eq!(
+            summary(&parse(hist, &InputOptions::default()).unwrap()),
+            vec![(String::new(), 4, 4001.791)]
+        );
+    }
+
+    #[test]
+    fn test_hey() {
+        let data = "response-time,DNS+dialup,DNS,Request-write,Response-delay,Response-read,status-code,offset\n\
+                    0.0123,0.0010,0.0000,0.0000,0.0110,0.0001,200,0.0015\n\
+                    0.0051,0.0000,0.0000,0.0000,0.0050,0.0001,200,0.0112\n";
+
+        assert_eq!(
+            summary(&parse(data, &InputOptions::default()).unwrap()),
+            vec![(String::new(), 2, 12304.383)]
+        );
+    }
+
+    #[test]
+    fn test_histf() {
+        let mut histogram = Histogram::<u64>::new(3).unwrap();
+        for value in 1..=100 {
+            histogram.record(value * 10).unwrap();
+        }
+
+        let mut bytes = vec![];
+        V2DeflateSerializer::new()
+            .serialize(&histogram, &mut bytes)
+            .unwrap();
+        let payload = base64::encode(&bytes);
+
+        let hgrm = &parse(&payload, &InputOptions::default()).unwrap()[0];
+        assert_eq!(hgrm.name, None);
+        assert_eq!(hgrm.total_count, 100);
+        assert_eq!(hgrm.value_at_percentile(0.5), 500.0);
+        assert_eq!(hgrm.max, 1000.0);
+
+        let log = format!(
+            "#[StartTime: 1.000 (seconds since epoch)]\n\
+             \"StartTimestamp\",\"Interval_Length\",\"Interval_Max\",\"Interval_Compressed_Histogram\"\n\
+             Tag=a,0.1,1.0,1.0,{payload}\n\
+             Tag=b,0.1,1.0,1.0,{payload}\n\
+             Tag=a,1.1,1.0,1.0,{payload}\n"
+        );
+
+        assert_eq!(
+            summary(&parse(&log, &InputOptions::default()).unwrap()),
+            vec![
+                ("a".to_string(), 200, 1000.0),
+                ("b".to_string(), 100, 1000.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Renders many charts in one run, in parallel, from a manifest.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use crate::detect::Format;
+use crate::hgrm::HGRMs;
+use crate::input::{self, InputOptions};
+use crate::renderer::{Renderer, RendererInput};
+use crate::units;
+
+/// A manifest of charts, each with its inputs, output and the same keys as
+/// a config file. Keys in `defaults` apply to every chart which doesn't set
+/// them itself, and relative paths are relative to the manifest:
+///
+/// ```toml
+/// [defaults]
+/// renderer = "violin"
+/// theme = "dark"
+///
+/// [[chart]]
+/// inputs = ["checkout/12k.hgrm", "checkout/24k.hgrm"]
+/// output = "charts/checkout.svg"
+/// title = "Checkout"
+///
+/// [[chart]]
+/// inputs = ["search"]
+/// output = "charts/search.png"
+/// renderer = "line"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Manifest {
+    defaults: Map<String, Value>,
+    #[serde(rename = "chart", alias = "charts")]
+    charts: Vec<Map<String, Value>>,
+}
+
+/// How a chart's inputs are read, as `InputArgs` on the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct InputConfig {
+    significant_digits: Option<u32>,
+    column: Option<String>,
+    unit: Option<String>,
+    input_format: Option<Format>,
+}
+
+impl InputConfig {
+    fn options(&self) -> Result<InputOptions, String> {
+        let defaults = InputOptions::default();
+        let significant_digits = self
+            .significant_digits
+            .unwrap_or(defaults.significant_digits);
+
+        if !(1..=5).contains(&significant_digits) {
+            return Err(format!(
+                "Significant digits `{}` must be between 1 and 5",
+                significant_digits
+            ));
+        }
+
+        Ok(InputOptions {
+            significant_digits,
+            column: self.column.clone(),
+            unit: self.unit.as_deref().map(units::parse_unit).transpose()?,
+            format: self.input_format,
+            ..defaults
+        })
+    }
+}
+
+/// One chart to render.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Job {
+    inputs: Vec<String>,
+    output: String,
+
+    #[serde(flatten)]
+    input: InputConfig,
+
+    #[serde(flatten)]
+    config: Config,
+}
+
+impl Job {
+    /// Loads and renders the chart, resolving paths against `base`.
+    fn run(&self, base: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let options = self.input.options()?;
+        let mut hgrms = vec![];
+
+        for input in &self.inputs {
+            let input = resolve(base, input);
+            if !Path::new(&input).exists() {
+                return Err(format!("No such file or directory {}", input).into());
+            }
+
+            hgrms.extend(input::load(&input, &options)?);
+        }
+
+        let output = resolve(base, &self.output);
+        if let Some(parent) = Path::new(&output).parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let renderer = Renderer::new(
+            self.config.renderer.clone().unwrap_or(RendererInput::Line),
+            HGRMs::new(hgrms),
+            self.config.options.clone(),
+        );
+
+        renderer.render(&output)
+    }
+}
+
+/// A chart read from a manifest, or why it couldn't be read, labelled by its
+/// output.
+#[derive(Debug)]
+struct Entry {
+    label: String,
+    job: Result<Job, String>,
+}
+
+/// Outcome of rendering one chart of a batch.
+#[derive(Debug)]
+struct Outcome {
+    label: String,
+    result: Result<u128, String>,
+}
+
+fn resolve(base: &Path, path: &str) -> String {
+    let path = Path::new(path);
+
+    if path.is_absolute() {
+        path.to_string_lossy().into_owned()
+    } else {
+        base.join(path).to_string_lossy().into_owned()
+    }
+}
+
+/// Reads the charts of a TOML, JSON or YAML manifest, by its extension.
+/// Charts which can't be read are returned as errors, labelled by position.
+fn load(path: &str) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path)?;
+
+    let manifest: Manifest = match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?,
+        Some("yaml" | "yml") => {
+            serde_yaml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?
+        }
+        _ => toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?,
+    };
+
+    if manifest.charts.is_empty() {
+        return Err(format!("{}: No charts found", path).into());
+    }
+
+    Ok(manifest
+        .charts
+        .into_iter()
+        .enumerate()
+        .map(|(idx, chart)| {
+            let label = match chart.get("output") {
+                Some(Value::String(output)) => output.clone(),
+                _ => format!("chart {}", idx + 1),
+            };
+
+            let mut merged = manifest.defaults.clone();
+            merged.extend(chart);
+
+            Entry {
+                label,
+                job: serde_json::from_value(Value::Object(merged)).map_err(|e| e.to_string()),
+            }
+        })
+        .collect())
+}
+
+/// Renders every chart of the manifest at `path`, using `jobs` threads or
+/// one per CPU, reporting how each went.
+fn run(path: &str, jobs: Option<usize>) -> Result<Vec<Outcome>, Box<dyn std::error::Error>> {
+    let charts = load(path)?;
+    let base = Path::new(path)
+        .parent()
+        .map_or_else(PathBuf::new, Path::to_path_buf);
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()?;
+
+    Ok(pool.install(|| {
+        charts
+            .par_iter()
+            .map(|entry| {
+                let started = Instant::now();
+                let result = entry.job.as_ref().map_err(String::clone).and_then(|job| {
+                    job.run(&base)
+                        .map(|_| started.elapsed().as_millis())
+                        .map_err(|e| e.to_string())
+                });
+
+                Outcome {
+                    label: entry.label.clone(),
+                    result,
+                }
+            })
+            .collect()
+    }))
+}
+
+/// Renders every chart of the manifest at `path` and prints a summary,
+/// failing when any chart couldn't be rendered.
+pub fn batch(path: &str, jobs: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let outcomes = run(path, jobs)?;
+
+    for outcome in &outcomes {
+        match &outcome.result {
+            Ok(millis) => println!("ok     {} ({} ms)", outcome.label, millis),
+            Err(e) => println!("FAILED {}: {}", outcome.label, e),
+        }
+    }
+
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+
+    println!(
+        "\nRendered {} of {} charts, {} failed",
+        outcomes.len() - failed,
+        outcomes.len(),
+        failed
+    );
+
+    if failed > 0 {
+        return Err(format!("{} of {} charts failed", failed, outcomes.len()).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    const HGRM: &str = include_str!("../test/data/12Krps.hgrm");
+
+    #[test]
+    fn test_load_merges_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("manifest.toml");
+        fs::write(
+            &manifest,
+            r#"
+[defaults]
+renderer = "violin"
+title = "Nightly"
+unit = "ms"
+
+[[chart]]
+inputs = ["a.hgrm"]
+output = "a.svg"
+
+[[chart]]
+inputs = ["b.hgrm"]
+output = "b.svg"
+renderer = "line"
+significant-digits = 2
+
+[[chart]]
+output = "missing-inputs.svg"
+"#,
+        )
+        .unwrap();
+
+        let charts = load(&manifest.to_string_lossy()).unwrap();
+        let labels: Vec<&str> = charts.iter().map(|chart| chart.label.as_str()).collect();
+        assert_eq!(labels, vec!["a.svg", "b.svg", "missing-inputs.svg"]);
+
+        let a = charts[0].job.as_ref().unwrap();
+        assert_eq!(a.config.renderer, Some(RendererInput::Violin));
+        assert_eq!(a.config.options.title.as_deref(), Some("Nightly"));
+        assert_eq!(a.input.options().unwrap().unit, Some(1000.0));
+
+        let b = charts[1].job.as_ref().unwrap();
+        assert_eq!(b.config.renderer, Some(RendererInput::Line));
+        assert_eq!(b.config.options.title.as_deref(), Some("Nightly"));
+        assert_eq!(b.input.significant_digits, Some(2));
+
+        assert!(charts[2].job.is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.hgrm"), HGRM).unwrap();
+        let manifest = dir.path().join("manifest.json");
+        fs::write(
+            &manifest,
+            r#"{
+  "defaults": {"renderer": "violin"},
+  "charts": [
+    {"inputs": ["a.hgrm"], "output": "out/a.svg"},
+    {"inputs": ["a.hgrm"], "output": "out/a.png", "renderer": "line"},
+    {"inputs": ["missing.hgrm"], "output": "out/missing.svg"}
+  ]
+}"#,
+        )
+        .unwrap();
+
+        let outcomes = run(&manifest.to_string_lossy(), Some(2)).unwrap();
+        let results: Vec<(&str, bool)> = outcomes
+            .iter()
+            .map(|outcome| (outcome.label.as_str(), outcome.result.is_ok()))
+            .collect();
+
+        assert_eq!(
+            results,
+            vec![
+                ("out/a.svg", true),
+                ("out/a.png", true),
+                ("out/missing.svg", false)
+            ]
+        );
+        assert!(dir.path().join("out/a.svg").is_file());
+        assert!(dir.path().join("out/a.png").is_file());
+    }
+}
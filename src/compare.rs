@@ -0,0 +1,637 @@
+//! Tests whether two latency distributions differ by more than noise, on the
+//! distributions reconstructed from their percentile rows.
+
+use std::f64::consts::PI;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::hgrm::HGRM;
+use crate::units::{format_percentile, LatencyUnit, DEFAULT_PERCENTILES};
+
+/// How two distributions are compared.
+#[derive(Clone, Debug)]
+pub struct CompareOptions {
+    /// Percentiles (0.0 - 1.0] given bootstrap confidence intervals.
+    pub percentiles: Vec<f64>,
+    /// Confidence level of the intervals, and `1 - confidence` the
+    /// significance level of the tests.
+    pub confidence: f64,
+    /// Number of bootstrap resamples.
+    pub iterations: usize,
+    pub mann_whitney: bool,
+    /// Seed of the bootstrap, so results are reproducible.
+    pub seed: u64,
+    /// Unit percentiles and differences are formatted in.
+    pub unit: LatencyUnit,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            percentiles: DEFAULT_PERCENTILES.to_vec(),
+            confidence: 0.95,
+            iterations: 1000,
+            mann_whitney: false,
+            seed: 0,
+            unit: LatencyUnit::default(),
+        }
+    }
+}
+
+/// Two-sample Kolmogorov–Smirnov test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KolmogorovSmirnov {
+    /// Largest distance between the two cumulative distributions.
+    pub statistic: f64,
+    pub p_value: f64,
+}
+
+/// Mann–Whitney U test, by its normal approximation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MannWhitney {
+    /// U of the candidate, the number of pairs in which it's slower.
+    pub u: f64,
+    pub z: f64,
+    pub p_value: f64,
+    /// Probability that a candidate request is slower than a baseline one.
+    pub effect: f64,
+}
+
+/// An estimate with its confidence interval, in microseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub estimate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+/// Bootstrap confidence intervals of a percentile of both distributions and
+/// of their difference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PercentileComparison {
+    pub percentile: f64,
+    pub baseline: Interval,
+    pub candidate: Interval,
+    /// Candidate minus baseline.
+    pub difference: Interval,
+}
+
+impl PercentileComparison {
+    /// Whether the interval of the difference excludes zero.
+    pub fn is_significant(&self) -> bool {
+        self.difference.low > 0.0 || self.difference.high < 0.0
+    }
+}
+
+/// Outcome of comparing a candidate distribution against a baseline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub confidence: f64,
+    pub kolmogorov_smirnov: KolmogorovSmirnov,
+    pub mann_whitney: Option<MannWhitney>,
+    pub percentiles: Vec<PercentileComparison>,
+    pub unit: LatencyUnit,
+}
+
+impl Comparison {
+    fn alpha(&self) -> f64 {
+        1.0 - self.confidence
+    }
+
+    /// One line for each test, e.g. `Kolmogorov–Smirnov D = 0.041, p < 0.001:
+    /// significant`.
+    pub fn tests(&self) -> Vec<String> {
+        let ks = &self.kolmogorov_smirnov;
+        let mut tests = vec![format!(
+            "Kolmogorov–Smirnov D = {:.3}, {}: {}",
+            ks.statistic,
+            format_p(ks.p_value),
+            verdict(ks.p_value < self.alpha())
+        )];
+
+        if let Some(mw) = &self.mann_whitney {
+            tests.push(format!(
+                "Mann–Whitney P(slower) = {:.3}, z = {:.2}, {}: {}",
+                mw.effect,
+                mw.z,
+                format_p(mw.p_value),
+                verdict(mw.p_value < self.alpha())
+            ));
+        }
+
+        tests
+    }
+
+    /// One line for each percentile's difference, e.g. `p99 +0.4ms (+0.1ms
+    /// to +0.7ms): significant`.
+    pub fn differences(&self) -> Vec<String> {
+        self.percentiles
+            .iter()
+            .map(|comparison| {
+                let difference = &comparison.difference;
+
+                format!(
+                    "{} {} ({} to {}): {}",
+                    format_percentile(comparison.percentile),
+                    format_difference(difference.estimate, self.unit),
+                    format_difference(difference.low, self.unit),
+                    format_difference(difference.high, self.unit),
+                    verdict(comparison.is_significant())
+                )
+            })
+            .collect()
+    }
+
+    /// Table of each percentile of both distributions and their difference,
+    /// with confidence intervals, headed by the names of the two series.
+    pub fn table(&self, baseline: &str, candidate: &str) -> String {
+        let interval = |interval: &Interval, format: &dyn Fn(f64) -> String| {
+            format!(
+                "{} ({} to {})",
+                format(interval.estimate),
+                format(interval.low),
+                format(interval.high)
+            )
+        };
+
+        let rows: Vec<[String; 5]> = std::iter::once([
+            "Percentile".to_string(),
+            baseline.to_string(),
+            candidate.to_string(),
+            "Difference".to_string(),
+            String::new(),
+        ])
+        .chain(self.percentiles.iter().map(|comparison| {
+            [
+                format_percentile(comparison.percentile),
+                interval(&comparison.baseline, &|value| self.unit.format(value)),
+                interval(&comparison.candidate, &|value| self.unit.format(value)),
+                interval(&comparison.difference, &|value| {
+                    format_difference(value, self.unit)
+                }),
+                verdict(comparison.is_significant()).to_string(),
+            ]
+        }))
+        .collect();
+
+        let widths: Vec<usize> = (0..5)
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        rows.iter()
+            .map(|row| {
+                let line: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                    .collect();
+
+                format!("{}\n", line.join("  ").trim_end())
+            })
+            .collect()
+    }
+}
+
+fn verdict(significant: bool) -> &'static str {
+    if significant {
+        "significant"
+    } else {
+        "not significant"
+    }
+}
+
+fn format_p(p: f64) -> String {
+    if p < 0.001 {
+        "p < 0.001".to_string()
+    } else {
+        format!("p = {:.3}", p)
+    }
+}
+
+/// Formats a signed difference in microseconds in `unit`, e.g. `+0.4ms`.
+fn format_difference(value: f64, unit: LatencyUnit) -> String {
+    if value < 0.0 {
+        format!("-{}", unit.format(-value))
+    } else {
+        format!("+{}", unit.format(value))
+    }
+}
+
+/// A histogram's cumulative distribution, interpolated linearly between its
+/// percentile rows. Rows are sparse and fall at different values in each
+/// histogram, so treating them as steps would make two samples of the same
+/// distribution look different.
+struct Distribution {
+    /// Values with the number of requests at or below each, starting at the
+    /// minimum with none.
+    knots: Vec<(f64, f64)>,
+}
+
+impl Distribution {
+    fn new(histogram: &HGRM) -> Self {
+        let buckets = histogram.bucket_counts();
+        let mut knots = vec![];
+        let mut total = 0.0;
+
+        if let Some((min, _)) = buckets.first() {
+            knots.push((*min, 0.0));
+        }
+
+        for (value, count) in buckets {
+            total += count as f64;
+            knots.push((value, total));
+        }
+
+        Self { knots }
+    }
+
+    fn total(&self) -> f64 {
+        self.knots.last().map_or(0.0, |(_, count)| *count)
+    }
+
+    /// Share of requests at or below `value`.
+    fn cdf(&self, value: f64) -> f64 {
+        let idx = self.knots.partition_point(|(v, _)| *v <= value);
+
+        let count = match (
+            idx.checked_sub(1).map(|i| self.knots[i]),
+            self.knots.get(idx),
+        ) {
+            (None, _) => 0.0,
+            (Some((_, count)), None) => count,
+            (Some((v0, c0)), Some((v1, c1))) => c0 + (c1 - c0) * (value - v0) / (v1 - v0),
+        };
+
+        count / self.total()
+    }
+
+    /// Mean share of requests at or below values evenly spread between
+    /// `from` and `to`.
+    fn mean_cdf(&self, from: f64, to: f64) -> f64 {
+        if to <= from {
+            return self.cdf(from);
+        }
+
+        // The cdf is linear between knots, so trapezoids between them are exact
+        let points: Vec<f64> = std::iter::once(from)
+            .chain(
+                self.knots
+                    .iter()
+                    .map(|(value, _)| *value)
+                    .filter(|value| *value > from && *value < to),
+            )
+            .chain(std::iter::once(to))
+            .collect();
+
+        points
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]) * (self.cdf(pair[0]) + self.cdf(pair[1])) / 2.0)
+            .sum::<f64>()
+            / (to - from)
+    }
+
+    /// Value below which `rank` requests fall.
+    fn value_at_rank(&self, rank: f64) -> f64 {
+        let idx = self.knots.partition_point(|(_, count)| *count < rank);
+
+        match (
+            idx.checked_sub(1).map(|i| self.knots[i]),
+            self.knots.get(idx),
+        ) {
+            (_, None) => self.knots.last().map_or(0.0, |(value, _)| *value),
+            (None, Some((value, _))) => *value,
+            (Some((v0, c0)), Some((v1, c1))) => v0 + (v1 - v0) * (rank - c0) / (c1 - c0),
+        }
+    }
+
+    fn value_at_percentile(&self, percentile: f64) -> f64 {
+        self.value_at_rank(percentile * self.total())
+    }
+}
+
+/// Complementary error function, with a fractional error below 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+/// Probability that the Kolmogorov distribution exceeds `lambda`.
+fn kolmogorov(lambda: f64) -> f64 {
+    // The series converges too slowly to be of use this close to zero
+    if lambda < 0.2 {
+        return 1.0;
+    }
+
+    let mut sum = 0.0;
+    let mut sign = 1.0;
+
+    for j in 1..=100 {
+        let term = sign * 2.0 * (-2.0 * (j * j) as f64 * lambda * lambda).exp();
+        sum += term;
+
+        if term.abs() <= 1e-10 * sum.abs() {
+            return sum.clamp(0.0, 1.0);
+        }
+
+        sign = -sign;
+    }
+
+    1.0
+}
+
+/// Standard normal variate by the Box–Muller transform.
+fn normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// Bootstraps the value at `percentile` of `distribution`. The rank of a
+/// percentile in a resample of `n` requests is binomial, so it's drawn from
+/// its normal approximation instead of resampling every request.
+fn resample(distribution: &Distribution, percentile: f64, rng: &mut StdRng) -> f64 {
+    let n = distribution.total();
+    let rank = n * percentile + (n * percentile * (1.0 - percentile)).sqrt() * normal(rng);
+
+    distribution.value_at_rank(rank.clamp(0.0, n))
+}
+
+/// Lower and upper bounds holding `confidence` of sorted `samples`.
+fn bounds(samples: &[f64], confidence: f64) -> (f64, f64) {
+    let alpha = 1.0 - confidence;
+    let last = (samples.len() - 1) as f64;
+
+    (
+        samples[(alpha / 2.0 * last).floor() as usize],
+        samples[((1.0 - alpha / 2.0) * last).ceil() as usize],
+    )
+}
+
+fn bootstrap(
+    baseline: &Distribution,
+    candidate: &Distribution,
+    percentile: f64,
+    options: &CompareOptions,
+    rng: &mut StdRng,
+) -> PercentileComparison {
+    let mut baselines = Vec::with_capacity(options.iterations);
+    let mut candidates = Vec::with_capacity(options.iterations);
+    let mut differences = Vec::with_capacity(options.iterations);
+
+    for _ in 0..options.iterations {
+        let b = resample(baseline, percentile, rng);
+        let c = resample(candidate, percentile, rng);
+
+        baselines.push(b);
+        candidates.push(c);
+        differences.push(c - b);
+    }
+
+    let interval = |estimate: f64, samples: &mut Vec<f64>| {
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let (low, high) = bounds(samples, options.confidence);
+
+        Interval {
+            estimate,
+            low,
+            high,
+        }
+    };
+
+    let b = baseline.value_at_percentile(percentile);
+    let c = candidate.value_at_percentile(percentile);
+
+    PercentileComparison {
+        percentile,
+        baseline: interval(b, &mut baselines),
+        candidate: interval(c, &mut candidates),
+        difference: interval(c - b, &mut differences),
+    }
+}
+
+fn kolmogorov_smirnov(baseline: &Distribution, candidate: &Distribution) -> KolmogorovSmirnov {
+    // Both cdfs are linear between knots, so the largest distance is at one
+    let statistic = baseline
+        .knots
+        .iter()
+        .chain(&candidate.knots)
+        .map(|(value, _)| (baseline.cdf(*value) - candidate.cdf(*value)).abs())
+        .fold(0.0, f64::max);
+
+    let (n, m) = (baseline.total(), candidate.total());
+    let effective = (n * m / (n + m)).sqrt();
+
+    KolmogorovSmirnov {
+        statistic,
+        p_value: kolmogorov((effective + 0.12 + 0.11 / effective) * statistic),
+    }
+}
+
+fn mann_whitney(baseline: &Distribution, candidate: &Distribution) -> MannWhitney {
+    let (n, m) = (baseline.total(), candidate.total());
+
+    // Candidate requests are spread evenly between its knots, each slower
+    // than the baseline's share of requests at or below it
+    let effect = candidate
+        .knots
+        .windows(2)
+        .map(|pair| {
+            let ((v0, c0), (v1, c1)) = (pair[0], pair[1]);
+
+            (c1 - c0) * baseline.mean_cdf(v0, v1)
+        })
+        .sum::<f64>()
+        / m;
+
+    let u = effect * n * m;
+    let sigma = (n * m * (n + m + 1.0) / 12.0).sqrt();
+    let z = (u - n * m / 2.0) / sigma;
+
+    MannWhitney {
+        u,
+        z,
+        p_value: erfc(z.abs() / 2f64.sqrt()),
+        effect,
+    }
+}
+
+/// Compares `candidate` against `baseline`.
+pub fn compare(
+    baseline: &HGRM,
+    candidate: &HGRM,
+    options: &CompareOptions,
+) -> Result<Comparison, String> {
+    if !(options.confidence > 0.0 && options.confidence < 1.0) {
+        return Err(format!(
+            "Confidence `{}` must be between 0 and 1",
+            options.confidence
+        ));
+    }
+    if options.iterations == 0 {
+        return Err("Iterations must be at least 1".to_string());
+    }
+    if let Some(percentile) = options
+        .percentiles
+        .iter()
+        .find(|p| !(**p > 0.0 && **p <= 1.0))
+    {
+        return Err(format!(
+            "Percentile `{}` must be greater than 0 and at most 1",
+            percentile
+        ));
+    }
+
+    let baseline = Distribution::new(baseline);
+    let candidate = Distribution::new(candidate);
+
+    if baseline.total() == 0.0 || candidate.total() == 0.0 {
+        return Err("Unable to compare an empty histogram".to_string());
+    }
+
+    let mut rng = StdRng::seed_from_u64(options.seed);
+
+    Ok(Comparison {
+        confidence: options.confidence,
+        kolmogorov_smirnov: kolmogorov_smirnov(&baseline, &candidate),
+        mann_whitney: options
+            .mann_whitney
+            .then(|| mann_whitney(&baseline, &candidate)),
+        percentiles: options
+            .percentiles
+            .iter()
+            .map(|percentile| bootstrap(&baseline, &candidate, *percentile, options, &mut rng))
+            .collect(),
+        unit: options.unit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    /// `count` requests spread evenly between `from` and `from + width`.
+    fn uniform(from: f64, width: f64, count: u64) -> HGRM {
+        let buckets: Vec<(f64, u64)> = (0..100)
+            .map(|i| (from + width * i as f64 / 100.0, count / 100))
+            .collect();
+
+        HGRM::from_buckets(&buckets)
+    }
+
+    #[test]
+    fn test_erfc() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-7);
+        assert!((erfc(1.0) - 0.157299207).abs() < 1e-7);
+        assert!((erfc(-1.0) - 1.842700793).abs() < 1e-7);
+    }
+
+    #[test]
+    fn test_kolmogorov() {
+        assert_eq!(kolmogorov(0.1), 1.0);
+        assert!((kolmogorov(1.358) - 0.05).abs() < 1e-3);
+        assert!(kolmogorov(3.0) < 1e-6);
+    }
+
+    #[test]
+    fn test_identical_distributions() {
+        let histogram = uniform(1000.0, 1000.0, 10_000);
+        let options = CompareOptions {
+            mann_whitney: true,
+            ..Default::default()
+        };
+
+        let comparison = compare(&histogram, &histogram, &options).unwrap();
+        let mann_whitney = comparison.mann_whitney.unwrap();
+
+        assert_eq!(comparison.kolmogorov_smirnov.statistic, 0.0);
+        assert_eq!(comparison.kolmogorov_smirnov.p_value, 1.0);
+        assert!((mann_whitney.effect - 0.5).abs() < 1e-3);
+        assert!(mann_whitney.p_value > 0.99);
+        assert!(comparison.percentiles.iter().all(|p| !p.is_significant()));
+    }
+
+    #[test]
+    fn test_shifted_distributions() {
+        let baseline = uniform(1000.0, 1000.0, 10_000);
+        let candidate = uniform(1100.0, 1000.0, 10_000);
+        let options = CompareOptions {
+            percentiles: vec![0.5, 0.99],
+            mann_whitney: true,
+            ..Default::default()
+        };
+
+        let comparison = compare(&baseline, &candidate, &options).unwrap();
+        let mann_whitney = comparison.mann_whitney.unwrap();
+
+        assert!(comparison.kolmogorov_smirnov.statistic >= 0.1);
+        assert!(comparison.kolmogorov_smirnov.p_value < 0.001);
+        assert!(mann_whitney.effect > 0.5);
+        assert!(mann_whitney.p_value < 0.001);
+
+        let median = &comparison.percentiles[0];
+        assert_eq!(median.difference.estimate, 100.0);
+        assert!(median.is_significant());
+        assert_eq!(
+            comparison.differences(),
+            vec![
+                "p50 +0.1ms (+0.086ms to +0.114ms): significant",
+                "p99 +0.1ms (+0.097ms to +0.103ms): significant",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_small_samples_are_not_significant() {
+        let baseline = HGRM::from_buckets(&[(1000.0, 2), (1100.0, 2), (1300.0, 1)]);
+        let candidate = HGRM::from_buckets(&[(1000.0, 1), (1100.0, 2), (1400.0, 2)]);
+
+        let comparison = compare(&baseline, &candidate, &CompareOptions::default()).unwrap();
+
+        assert!(comparison.kolmogorov_smirnov.p_value > 0.05);
+        assert!(!comparison.percentiles[0].is_significant());
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let histogram = uniform(1000.0, 1000.0, 100);
+        let compare_with = |options: CompareOptions| compare(&histogram, &histogram, &options);
+
+        assert!(compare_with(CompareOptions {
+            confidence: 1.0,
+            ..Default::default()
+        })
+        .is_err());
+        assert!(compare_with(CompareOptions {
+            percentiles: vec![0.0],
+            ..Default::default()
+        })
+        .is_err());
+        assert!(compare(&HGRM::new(), &histogram, &CompareOptions::default()).is_err());
+    }
+}
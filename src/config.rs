@@ -0,0 +1,197 @@
+use std::{fs, path::Path};
+
+use plotters::style::RGBColor;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::density::Bandwidth;
+use crate::renderer::{RenderOptions, RendererInput};
+use crate::slo::Slo;
+use crate::theme::parse_color;
+use crate::units::{check_percentile, parse_duration};
+
+/// A chart configuration file, rendering options are given at the top level
+/// alongside the renderer:
+///
+/// ```toml
+/// renderer = "line"
+/// theme = "dark"
+/// slo = ["0.99:50ms", "0.999:200ms"]
+/// latency-unit = "ms"
+///
+/// [[series]]
+/// name = "12Krps"
+/// label = "12K requests/s"
+/// color = "#1f78b4"
+/// line-style = "dashed"
+/// facet = "checkout"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub renderer: Option<RendererInput>,
+
+    #[serde(flatten)]
+    pub options: RenderOptions,
+}
+
+impl Config {
+    /// Loads a TOML file, or YAML when the extension is `.yaml` or `.yml`.
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = fs::read_to_string(path)?;
+
+        let yaml = matches!(
+            Path::new(path).extension().and_then(|e| e.to_str()),
+            Some("yaml" | "yml")
+        );
+
+        let config = if yaml {
+            serde_yaml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?
+        } else {
+            toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?
+        };
+
+        Ok(config)
+    }
+}
+
+pub fn duration<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| parse_duration(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+pub fn percentiles<'de, D>(deserializer: D) -> Result<Vec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<f64>::deserialize(deserializer)?
+        .into_iter()
+        .map(|p| check_percentile(p).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+pub fn slos<'de, D>(deserializer: D) -> Result<Vec<Slo>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+pub fn bandwidth<'de, D>(deserializer: D) -> Result<Option<Bandwidth>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+pub fn color<'de, D>(deserializer: D) -> Result<Option<RGBColor>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| parse_color(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+pub fn regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| Regex::new(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::LineStyle;
+    use crate::theme::Theme;
+    use crate::units::LatencyUnit;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_toml() {
+        let config: Config = toml::from_str(
+            r##"
+renderer = "violin"
+theme = "okabe-ito"
+slo = ["0.99:50ms"]
+annotate = [0.99, 0.999]
+log-y = true
+y-max = "200ms"
+latency-unit = "us"
+clamp = false
+
+[[series]]
+name = "12Krps"
+label = "12K"
+color = "#ff0000"
+line-style = "dashed"
+
+[[series]]
+name = "16Krps"
+hidden = true
+"##,
+        )
+        .unwrap();
+
+        assert!(matches!(config.renderer, Some(RendererInput::Violin)));
+        assert_eq!(config.options.theme, Theme::OkabeIto);
+        assert_eq!(config.options.slos, vec![Slo::new(0.99, 50_000.0)]);
+        assert_eq!(config.options.annotate, vec![0.99, 0.999]);
+        assert!(config.options.log_y);
+        assert_eq!(config.options.y_max, Some(200_000.0));
+        assert_eq!(config.options.latency_unit, LatencyUnit::Us);
+        assert_eq!(config.options.clamp, Some(false));
+        assert_eq!(config.options.series.len(), 2);
+        assert_eq!(config.options.series[0].label, Some("12K".to_string()));
+        assert_eq!(config.options.series[0].color, Some(RGBColor(255, 0, 0)));
+        assert_eq!(config.options.series[0].line_style, Some(LineStyle::Dashed));
+        assert!(config.options.series[1].hidden);
+    }
+
+    #[test]
+    fn test_yaml() {
+        let config: Config = serde_yaml::from_str(
+            r##"
+theme: dark
+table: true
+series:
+  - name: 12Krps
+    color: "#00ff00"
+"##,
+        )
+        .unwrap();
+
+        assert!(config.renderer.is_none());
+        assert_eq!(config.options.theme, Theme::Dark);
+        assert!(config.options.table);
+        assert_eq!(config.options.series[0].color, Some(RGBColor(0, 255, 0)));
+    }
+
+    #[test]
+    fn test_invalid_duration() {
+        let config: Result<Config, _> = toml::from_str(r#"y-min = "5 parsecs""#);
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_invalid_annotate() {
+        let config: Result<Config, _> = toml::from_str("annotate = [0.99, 5.0]");
+
+        assert!(config.is_err());
+    }
+}
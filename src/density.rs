@@ -0,0 +1,302 @@
+use std::str::FromStr;
+
+use csaps::CubicSmoothingSpline;
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::hgrm::HGRM;
+use crate::units::{parse_duration, MICROS_PER_MILLI};
+
+const DEFAULT_SMOOTHING_FACTOR: f64 = 0.99;
+const KDE_POINTS: usize = 256;
+/// Height of the most common latency, leaving a gap between violins.
+const MAX_HEIGHT: f64 = 0.9;
+/// Half width of the peak drawn for a histogram of a single value, relative
+/// to that value, so it stays visible.
+const SINGLE_VALUE_SPREAD: f64 = 0.02;
+
+/// How the density of a histogram is estimated for violins.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Smoothing {
+    /// Cubic smoothing spline through the bucket counts
+    #[default]
+    Spline,
+    /// Gaussian kernel density estimate
+    Kde,
+}
+
+/// Bandwidth of the kernel density estimate, either a rule of thumb or a
+/// fixed duration in microseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bandwidth {
+    Silverman,
+    Scott,
+    Fixed(f64),
+}
+
+impl FromStr for Bandwidth {
+    type Err = String;
+
+    /// Parses `silverman`, `scott` or a duration, e.g. `50us`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "silverman" => Ok(Bandwidth::Silverman),
+            "scott" => Ok(Bandwidth::Scott),
+            duration => match parse_duration(duration)? {
+                bandwidth if bandwidth > 0.0 => Ok(Bandwidth::Fixed(bandwidth)),
+                _ => Err(format!("Bandwidth `{}` must be greater than 0", s)),
+            },
+        }
+    }
+}
+
+/// Estimated density of `histogram` as (microseconds, height) points, scaled
+/// so the most common latency is `MAX_HEIGHT` high. Overshoots of the
+/// smoothing below zero are clipped when `clamp` is set.
+pub fn density(
+    histogram: &HGRM,
+    smoothing: Smoothing,
+    smoothing_factor: Option<f64>,
+    bandwidth: Option<Bandwidth>,
+    clamp: bool,
+) -> Result<Vec<(f64, f64)>, Box<dyn std::error::Error>> {
+    let counts = counts(histogram);
+
+    let data = match counts.as_slice() {
+        [] => return Ok(vec![]),
+        [(x, _)] => {
+            let spread = (x.abs() * SINGLE_VALUE_SPREAD).max(1.0);
+
+            vec![(x - spread, 0.0), (*x, 1.0), (x + spread, 0.0)]
+        }
+        _ => match smoothing {
+            Smoothing::Spline => spline(&counts, smoothing_factor).map_err(|e| {
+                format!(
+                    "Unable to smooth histogram{}: {}",
+                    histogram
+                        .name
+                        .as_ref()
+                        .map_or(String::new(), |name| format!(" {}", name)),
+                    e
+                )
+            })?,
+            Smoothing::Kde => kde(&counts, bandwidth.unwrap_or(Bandwidth::Silverman)),
+        },
+    };
+
+    let max = data.iter().map(|(_, y)| *y).fold(0f64, f64::max);
+    let scale = if max > 0.0 { MAX_HEIGHT / max } else { 0.0 };
+
+    Ok(data
+        .into_iter()
+        .map(|(x, y)| (x, if clamp { y.max(0.0) } else { y } * scale))
+        .collect())
+}
+
+/// Number of samples recorded at each distinct latency.
+fn counts(histogram: &HGRM) -> Vec<(f64, f64)> {
+    let mut counts: Vec<(f64, f64)> = histogram
+        .percentiles
+        .iter()
+        .scan(0f64, |prev, percentile| {
+            let count_diff = (percentile.total_count as f64) - *prev;
+            *prev = percentile.total_count as f64;
+
+            Some((percentile.value, count_diff))
+        })
+        .filter(|(x, _)| x.is_finite())
+        .collect();
+
+    counts.sort_by(|(x1, _), (x2, _)| x1.total_cmp(x2));
+
+    counts
+        .into_iter()
+        .group_by(|(x, _y)| *x)
+        .into_iter()
+        .map(|(x, ys)| (x, ys.map(|(_, y)| y).sum()))
+        .collect()
+}
+
+fn spline(
+    counts: &[(f64, f64)],
+    smoothing_factor: Option<f64>,
+) -> Result<Vec<(f64, f64)>, Box<dyn std::error::Error>> {
+    let smoothing_factor = smoothing_factor.unwrap_or(DEFAULT_SMOOTHING_FACTOR);
+    if !(0.0..=1.0).contains(&smoothing_factor) {
+        return Err(format!(
+            "Smoothing factor `{}` must be between 0 and 1",
+            smoothing_factor
+        )
+        .into());
+    }
+
+    // Fitted in milliseconds, the scale smoothing factors were tuned for, as
+    // the roughness penalty depends on the scale of x
+    let xs: Vec<f64> = counts.iter().map(|(x, _y)| x / MICROS_PER_MILLI).collect();
+    let ys: Vec<f64> = counts.iter().map(|(_x, y)| *y).collect();
+
+    let smooth_ys = CubicSmoothingSpline::new(&xs, &ys)
+        .with_smooth(smoothing_factor)
+        .make()?
+        .evaluate(&xs)?;
+
+    Ok(counts
+        .iter()
+        .map(|(x, _)| *x)
+        .zip(smooth_ys.iter().copied())
+        .collect())
+}
+
+/// Gaussian kernel density estimate weighted by bucket counts, evaluated
+/// evenly across the recorded range.
+fn kde(counts: &[(f64, f64)], bandwidth: Bandwidth) -> Vec<(f64, f64)> {
+    let min = counts.first().map_or(0.0, |(x, _)| *x);
+    let max = counts.last().map_or(0.0, |(x, _)| *x);
+    let h = match bandwidth {
+        Bandwidth::Fixed(bandwidth) => bandwidth,
+        rule => rule_of_thumb(counts, rule),
+    };
+    // Degenerate spreads fall back to a bandwidth relative to the range
+    let h = if h > 0.0 {
+        h
+    } else {
+        ((max - min) / KDE_POINTS as f64).max(f64::EPSILON)
+    };
+
+    (0..KDE_POINTS)
+        .map(|i| min + (max - min) * i as f64 / (KDE_POINTS - 1) as f64)
+        .map(|x| {
+            let y = counts
+                .iter()
+                .map(|(xi, weight)| weight * (-0.5 * ((x - xi) / h).powi(2)).exp())
+                .sum::<f64>();
+
+            (x, y)
+        })
+        .collect()
+}
+
+fn rule_of_thumb(counts: &[(f64, f64)], rule: Bandwidth) -> f64 {
+    let n: f64 = counts.iter().map(|(_, weight)| weight).sum();
+    let mean = counts.iter().map(|(x, weight)| x * weight).sum::<f64>() / n;
+    let variance = counts
+        .iter()
+        .map(|(x, weight)| weight * (x - mean).powi(2))
+        .sum::<f64>()
+        / n;
+    let sd = variance.sqrt();
+
+    match rule {
+        Bandwidth::Scott => 1.06 * sd * n.powf(-0.2),
+        _ => {
+            let quantile = |q: f64| {
+                let mut seen = 0.0;
+                counts
+                    .iter()
+                    .find(|(_, weight)| {
+                        seen += weight;
+                        seen >= q * n
+                    })
+                    .map_or(0.0, |(x, _)| *x)
+            };
+            let iqr = quantile(0.75) - quantile(0.25);
+            let spread = if iqr > 0.0 { sd.min(iqr / 1.34) } else { sd };
+
+            0.9 * spread * n.powf(-0.2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_bandwidth() {
+        assert_eq!("silverman".parse(), Ok(Bandwidth::Silverman));
+        assert_eq!("scott".parse(), Ok(Bandwidth::Scott));
+        assert_eq!("2ms".parse(), Ok(Bandwidth::Fixed(2000.0)));
+        assert!("0ms".parse::<Bandwidth>().is_err());
+        assert!("wide".parse::<Bandwidth>().is_err());
+    }
+
+    #[test]
+    fn test_degenerate_histograms() {
+        let empty = HGRM::new();
+        assert_eq!(
+            density(&empty, Smoothing::Spline, None, None, true).unwrap(),
+            vec![]
+        );
+
+        // A single value is drawn as a narrow peak rather than a point
+        let single = HGRM::from_buckets(&[(1000.0, 5)]);
+        for smoothing in [Smoothing::Spline, Smoothing::Kde] {
+            assert_eq!(
+                density(&single, smoothing, None, None, true).unwrap(),
+                vec![(980.0, 0.0), (1000.0, MAX_HEIGHT), (1020.0, 0.0)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_clamp() {
+        let histogram = HGRM::from_buckets(&[(1.0, 1000), (2.0, 1), (3.0, 1000), (50.0, 1)]);
+
+        for smoothing in [Smoothing::Spline, Smoothing::Kde] {
+            let data = density(&histogram, smoothing, Some(1.0), None, true).unwrap();
+            let max = data.iter().map(|(_, y)| *y).fold(0f64, f64::max);
+
+            assert!(data.iter().all(|(_, y)| *y >= 0.0));
+            assert!((max - MAX_HEIGHT).abs() < 1e-9);
+        }
+
+        // The spline overshoots below zero after a sharp peak
+        let peak = HGRM::from_buckets(&[
+            (1000.0, 1),
+            (2000.0, 1000),
+            (3000.0, 1),
+            (4000.0, 1),
+            (5000.0, 1),
+        ]);
+        let negative = |clamp| {
+            density(&peak, Smoothing::Spline, None, None, clamp)
+                .unwrap()
+                .iter()
+                .any(|(_, y)| *y < 0.0)
+        };
+        assert!(negative(false));
+        assert!(!negative(true));
+    }
+
+    #[test]
+    fn test_fixed_bandwidth() {
+        let histogram = HGRM::from_buckets(&[(1000.0, 100), (3000.0, 100)]);
+        let midpoint = |bandwidth: &str| {
+            let data = density(
+                &histogram,
+                Smoothing::Kde,
+                None,
+                Some(bandwidth.parse().unwrap()),
+                true,
+            )
+            .unwrap();
+
+            data[KDE_POINTS / 2].1
+        };
+
+        // Values and bandwidths are both in microseconds
+        assert!(midpoint("100us") < 0.01);
+        assert!(midpoint("5ms") > 0.8);
+    }
+
+    #[test]
+    fn test_invalid_smoothing_factor() {
+        let histogram = HGRM::from_buckets(&[(1.0, 10), (2.0, 5)]);
+
+        assert!(density(&histogram, Smoothing::Spline, Some(1.5), None, true).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Sniffs how an input is encoded and which format it's written in, so it
+//! can be sent to the right decoder and parser.
+
+use std::fmt;
+
+use clap::ArgEnum;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::parser;
+
+/// Cookie starting base64 encoded HdrHistogram V2 compressed payloads.
+const HISTF_COOKIE: &str = "HISTF";
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Flags following a deflate zlib header for each compression level.
+const ZLIB_FLAGS: [u8; 4] = [0x01, 0x5e, 0x9c, 0xda];
+const ZLIB_DEFLATE: u8 = 0x78;
+
+/// Encodings wrapped around an input, removed before it's parsed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Base64,
+    Gzip,
+    Zlib,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Base64 => write!(f, "base64"),
+            Encoding::Gzip => write!(f, "gzip"),
+            Encoding::Zlib => write!(f, "zlib"),
+        }
+    }
+}
+
+/// Formats an input can be parsed from.
+#[derive(ArgEnum, Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// HdrHistogram percentile distribution, as printed by wrk2
+    Hgrm,
+    /// Base64 encoded HdrHistogram payloads, or an interval log of them
+    Histf,
+    K6,
+    Jmeter,
+    Gatling,
+    Fortio,
+    Ghz,
+    Vegeta,
+    Hey,
+    /// Raw samples from a CSV column, the first unless `--column` is given
+    Csv,
+    /// Raw samples, one per line
+    Samples,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Sniffs the outermost encoding of `data` from its magic bytes, or `None`
+/// when it's already plain text.
+pub fn encoding(data: &[u8]) -> Option<Encoding> {
+    match data {
+        [a, b, ..] if [*a, *b] == GZIP_MAGIC => Some(Encoding::Gzip),
+        _ if is_zlib(data) => Some(Encoding::Zlib),
+        _ if is_base64(data) => Some(Encoding::Base64),
+        _ => None,
+    }
+}
+
+fn is_zlib(data: &[u8]) -> bool {
+    matches!(data, [ZLIB_DEFLATE, flags, ..] if ZLIB_FLAGS.contains(flags))
+}
+
+/// Whether `data` only uses the base64 alphabet and decodes to something
+/// worth unwrapping further: compressed bytes or text. HdrHistogram payloads
+/// and numbers, which happen to use the same alphabet, are left alone.
+fn is_base64(data: &[u8]) -> bool {
+    let compact: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+
+    if compact.is_empty()
+        || compact.starts_with(HISTF_COOKIE.as_bytes())
+        || compact.iter().all(u8::is_ascii_digit)
+        || !compact
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || b"+/=".contains(b))
+    {
+        return false;
+    }
+
+    match base64::decode(&compact) {
+        Ok(decoded) => {
+            decoded.starts_with(&GZIP_MAGIC)
+                || is_zlib(&decoded)
+                || std::str::from_utf8(&decoded).is_ok_and(|text| {
+                    text.chars()
+                        .all(|c| !c.is_control() || c.is_ascii_whitespace())
+                })
+        }
+        Err(_) => false,
+    }
+}
+
+/// Guesses the format of decoded `data` from its contents, falling back to
+/// raw samples.
+pub fn format(data: &str) -> Format {
+    let first_line = data.trim_start().lines().next().unwrap_or("").trim();
+
+    if data.trim_start().starts_with(HISTF_COOKIE)
+        || data
+            .lines()
+            .any(|line| line.split(',').any(|field| field.starts_with(HISTF_COOKIE)))
+    {
+        return Format::Histf;
+    }
+
+    // wrk2 prints a summary of its own before the distribution, which the
+    // parser skips
+    if data.lines().any(parser::is_header) {
+        return Format::Hgrm;
+    }
+
+    // A single JSON document, or the first record of a JSON lines stream
+    let json = serde_json::from_str::<Value>(data)
+        .or_else(|_| serde_json::from_str::<Value>(first_line))
+        .ok()
+        .filter(Value::is_object);
+
+    if let Some(json) = json {
+        let has = |key: &str| json.get(key).is_some();
+
+        if has("DurationHistogram") {
+            Format::Fortio
+        } else if has("latencyDistribution") || (has("histogram") && has("rps")) {
+            Format::Ghz
+        } else if has("latencies") || (has("seq") && has("latency")) {
+            Format::Vegeta
+        } else {
+            Format::K6
+        }
+    } else if first_line.starts_with("RUN\t") || first_line.starts_with("REQUEST\t") {
+        Format::Gatling
+    } else if first_line.starts_with("response-time,") {
+        Format::Hey
+    } else if first_line.contains(',') && first_line.contains("elapsed") {
+        Format::Jmeter
+    } else if first_line.starts_with("Bucket") && first_line.contains('#') {
+        Format::Vegeta
+    } else if first_line.contains(',') {
+        Format::Csv
+    } else {
+        Format::Samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_encoding() {
+        let cases: [(&[u8], Option<Encoding>); 7] = [
+            (&[0x1f, 0x8b, 0x08, 0x00], Some(Encoding::Gzip)),
+            (&[0x78, 0x9c, 0x4b, 0x04], Some(Encoding::Zlib)),
+            (
+                b"H4sIAAAAAAAAA8tIzcnJBwCGphA2BQAAAA==",
+                Some(Encoding::Base64),
+            ),
+            (b"aGVsbG8gd29ybGQ=\n", Some(Encoding::Base64)),
+            (b"HISTFAAAACh4nJNpmSzMwMDAyAABzFCaEUoz2X+AsQG2RDwa", None),
+            (b"1234\n5678\n", None),
+            (b"x marks the spot", None),
+        ];
+
+        for (data, encoding) in cases {
+            assert_eq!(super::encoding(data), encoding, "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let cases = [
+            (
+                "       Value   Percentile   TotalCount 1/(1-Percentile)\n",
+                Format::Hgrm,
+            ),
+            (
+                "=== checkout ===\n Value   Percentile   TotalCount 1/(1-Percentile)\n",
+                Format::Hgrm,
+            ),
+            (
+                "# wrk2\r\nvalue percentile totalcount 1/(1-percentile)\r\n",
+                Format::Hgrm,
+            ),
+            (
+                &format!(
+                    "Running 30s test @ http://127.0.0.1:8080/\n{}  Detailed Percentile spectrum:\n       Value   Percentile   TotalCount 1/(1-Percentile)\n",
+                    "  Thread calibration: mean lat.: 1.329ms\n".repeat(16)
+                ),
+                Format::Hgrm,
+            ),
+            (
+                "HISTFAAAACh4nJNpmSzMwMDAyAABzFCaEUoz2X+AsQG2RDwa",
+                Format::Histf,
+            ),
+            (
+                "#[StartTime: 1.0]\nTag=a,0.1,1.0,2.0,HISTFAAAACh4nJNp\n",
+                Format::Histf,
+            ),
+            (r#"{"metrics": {}}"#, Format::K6),
+            (
+                r#"{"type":"Point","metric":"http_reqs","data":{}}"#,
+                Format::K6,
+            ),
+            (r#"{"DurationHistogram": {"Data": []}}"#, Format::Fortio),
+            (r#"{"name":"","rps":1,"histogram":[]}"#, Format::Ghz),
+            (r#"{"latencies": {}, "requests": 1}"#, Format::Vegeta),
+            (r#"{"seq":0,"latency":12000}"#, Format::Vegeta),
+            (
+                "Bucket  #  %  Histogram\n[0s, 1ms] 1 100% #\n",
+                Format::Vegeta,
+            ),
+            (
+                "response-time,DNS+dialup,status-code\n0.01,0.001,200\n",
+                Format::Hey,
+            ),
+            ("timeStamp,elapsed,label\n1,2,a\n", Format::Jmeter),
+            ("RUN\tsim\tsim\t1\t \t3.3.1\n", Format::Gatling),
+            ("latency,status\n12,200\n", Format::Csv),
+            ("12\n15\n", Format::Samples),
+        ];
+
+        for (data, format) in cases {
+            assert_eq!(super::format(data), format, "{}", data);
+        }
+    }
+}
@@ -1,6 +1,10 @@
-use std::{ops::Index, slice::Iter};
+use std::{fmt, ops::Index, slice::Iter};
 
-#[derive(Debug, PartialEq)]
+/// Largest rounding error of a percentile printed to 6 decimals.
+const PERCENTILE_PRECISION: f64 = 5.0e-7 + 1.0e-12;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HGRM {
     pub name: Option<String>,
     pub percentiles: Vec<Percentile>,
@@ -83,14 +87,343 @@ impl HGRM {
         self
     }
 
+    /// Multiplies every value by `factor`, converting them to another unit.
+    pub fn scale(mut self, factor: f64) -> Self {
+        for percentile in &mut self.percentiles {
+            percentile.value *= factor;
+        }
+
+        self.mean *= factor;
+        self.std_deviation *= factor;
+        self.max *= factor;
+        self
+    }
+
     pub fn max_latency(&self) -> f64 {
         self.percentiles
             .iter()
             .fold(f64::MIN, |a, b| a.max(b.value))
     }
+
+    pub fn min_latency(&self) -> f64 {
+        self.percentiles
+            .iter()
+            .fold(f64::MAX, |a, b| a.min(b.value))
+    }
+
+    /// Smallest recorded value such that `percentile` (0.0 - 1.0) of all
+    /// samples are less than or equal to it.
+    pub fn value_at_percentile(&self, percentile: f64) -> f64 {
+        let buckets = self.bucket_counts();
+        let total: u64 = buckets.iter().map(|(_, count)| count).sum();
+        let target = ((percentile * total as f64).ceil() as u64).max(1);
+
+        let mut cumulative = 0u64;
+        for (value, count) in &buckets {
+            cumulative += count;
+            if cumulative >= target {
+                return *value;
+            }
+        }
+
+        buckets.last().map(|(value, _)| *value).unwrap_or(0.0)
+    }
+
+    /// Reconstructs `(value, count)` buckets from the cumulative counts of the
+    /// percentile rows. Rows sharing a value are folded into one bucket.
+    pub fn bucket_counts(&self) -> Vec<(f64, u64)> {
+        let mut buckets: Vec<(f64, u64)> = vec![];
+        let mut prev = 0u64;
+
+        for percentile in &self.percentiles {
+            let count = percentile.total_count.saturating_sub(prev);
+            prev = prev.max(percentile.total_count);
+
+            match buckets.last_mut() {
+                Some((value, c)) if *value == percentile.value => *c += count,
+                _ if count == 0 => (),
+                _ => buckets.push((percentile.value, count)),
+            }
+        }
+
+        buckets
+    }
+
+    /// Builds a histogram from `(value, count)` buckets sorted by value,
+    /// emitting the same percentile spectrum as HdrHistogram's
+    /// `outputPercentileDistribution` with 5 ticks per half distance.
+    pub fn from_buckets(buckets: &[(f64, u64)]) -> Self {
+        let total_count: u64 = buckets.iter().map(|(_, count)| count).sum();
+
+        if total_count == 0 {
+            return Self::new();
+        }
+
+        let total = total_count as f64;
+        let mean = buckets.iter().map(|(v, c)| v * *c as f64).sum::<f64>() / total;
+        let variance = buckets
+            .iter()
+            .map(|(v, c)| (v - mean).powi(2) * *c as f64)
+            .sum::<f64>()
+            / total;
+        let max = buckets.iter().fold(f64::MIN, |a, (v, _)| a.max(*v));
+
+        let mut percentiles = vec![];
+        let mut percentile_to_iterate_to = 0f64;
+        let mut cumulative = 0u64;
+
+        for (value, count) in buckets.iter().filter(|(_, count)| *count > 0) {
+            cumulative += count;
+            let current_percentile = 100.0 * cumulative as f64 / total;
+
+            while percentile_to_iterate_to <= current_percentile {
+                let percentile = percentile_to_iterate_to / 100.0;
+                percentiles.push(Percentile::new(
+                    *value,
+                    percentile,
+                    cumulative,
+                    OnePercentile::Value(1.0 / (1.0 - percentile)),
+                ));
+
+                let half_distance =
+                    2f64.powi((100.0 / (100.0 - percentile_to_iterate_to)).log2() as i32 + 1);
+                percentile_to_iterate_to += 100.0 / (5.0 * half_distance);
+
+                if cumulative == total_count {
+                    break;
+                }
+            }
+        }
+
+        percentiles.push(Percentile::new(max, 1.0, total_count, OnePercentile::Inf));
+
+        Self::new()
+            .set_mean(mean)
+            .set_std_deviation(variance.sqrt())
+            .set_max(max)
+            .set_total_count(total_count)
+            .set_percentiles(percentiles)
+    }
+
+    /// Checks the histogram is consistent with itself, as a corrupted or
+    /// truncated file may still parse: values, percentiles and counts must
+    /// not decrease, the last row must account for `Total count` and `Max`,
+    /// and `1/(1-Percentile)` must match its percentile.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+
+        let Some(last) = self.percentiles.last() else {
+            return vec![Problem::new(None, "no percentile rows")];
+        };
+
+        for (idx, row) in self.percentiles.iter().enumerate() {
+            let row_number = Some(idx + 1);
+
+            if !(0.0..=1.0).contains(&row.percentile) {
+                problems.push(Problem::new(
+                    row_number,
+                    format!("percentile {} is outside 0 to 1", row.percentile),
+                ));
+            }
+
+            if let Some(prev) = idx.checked_sub(1).map(|i| &self.percentiles[i]) {
+                if row.value < prev.value {
+                    problems.push(Problem::new(
+                        row_number,
+                        format!(
+                            "value {} is below the previous row's {}",
+                            row.value, prev.value
+                        ),
+                    ));
+                }
+                if row.percentile < prev.percentile {
+                    problems.push(Problem::new(
+                        row_number,
+                        format!(
+                            "percentile {} is below the previous row's {}",
+                            row.percentile, prev.percentile
+                        ),
+                    ));
+                }
+                if row.total_count < prev.total_count {
+                    problems.push(Problem::new(
+                        row_number,
+                        format!(
+                            "total count {} is below the previous row's {}",
+                            row.total_count, prev.total_count
+                        ),
+                    ));
+                }
+            }
+
+            match row.one_percentile {
+                OnePercentile::Inf if row.percentile < 1.0 => problems.push(Problem::new(
+                    row_number,
+                    format!("1/(1-Percentile) is inf for percentile {}", row.percentile),
+                )),
+                OnePercentile::Value(value) => {
+                    // Compared as percentiles, which are printed to as few as
+                    // 6 decimals, making 1/(1-Percentile) imprecise near 1,
+                    // allowing for its own rounding to 2 decimals
+                    let implied = 1.0 - 1.0 / value;
+                    let tolerance = PERCENTILE_PRECISION + 0.005 / (value * value);
+                    let difference = (implied - row.percentile).abs();
+
+                    if difference.is_nan() || difference > tolerance {
+                        problems.push(Problem::new(
+                            row_number,
+                            format!(
+                                "1/(1-Percentile) {} doesn't match percentile {}, expected {:.2}",
+                                value,
+                                row.percentile,
+                                1.0 / (1.0 - row.percentile)
+                            ),
+                        ));
+                    }
+                }
+                OnePercentile::Inf => (),
+            }
+        }
+
+        let last_row = Some(self.percentiles.len());
+
+        if last.total_count != self.total_count {
+            problems.push(Problem::new(
+                last_row,
+                format!(
+                    "total count {} doesn't match Total count {}",
+                    last.total_count, self.total_count
+                ),
+            ));
+        }
+
+        // HdrHistogram reports the highest value equivalent to the max in
+        // the last row, which may differ from it by up to a sub bucket
+        let precision = match self.sub_buckets {
+            0 => 1e-3,
+            sub_buckets => 2.0 / sub_buckets as f64,
+        };
+        if (last.value - self.max).abs() > 1e-3 + last.value.max(self.max) * precision {
+            problems.push(Problem::new(
+                last_row,
+                format!("value {} doesn't match Max {}", last.value, self.max),
+            ));
+        }
+
+        problems
+    }
+
+    /// Applies coordinated omission correction the way HdrHistogram's
+    /// `recordValueWithExpectedInterval` does at record time: every value
+    /// larger than `expected_interval` also records the samples that were
+    /// missed while waiting, at `value - interval`, `value - 2 * interval`, …
+    /// down to `expected_interval`.
+    ///
+    /// The missing samples are accumulated into the reconstructed buckets,
+    /// each bucket covering the values above the previous bucket's value.
+    pub fn correct_coordinated_omission(&self, expected_interval: f64) -> Self {
+        let buckets = self.bucket_counts();
+
+        if expected_interval <= 0.0 {
+            return self.clone();
+        }
+
+        let mut corrected: Vec<(f64, u64)> = buckets.clone();
+
+        for &(value, count) in &buckets {
+            let max_k = ((value - expected_interval) / expected_interval).floor();
+
+            if max_k < 1.0 {
+                continue;
+            }
+
+            let mut lower = f64::NEG_INFINITY;
+
+            for (target, (upper, _)) in buckets.iter().enumerate() {
+                if lower >= value {
+                    break;
+                }
+
+                // Steps k such that lower < value - k * interval <= upper.
+                let first = ((value - upper) / expected_interval).ceil().max(1.0);
+                let last = (((value - lower) / expected_interval).ceil() - 1.0).min(max_k);
+
+                if last >= first {
+                    corrected[target].1 += (last - first + 1.0) as u64 * count;
+                }
+
+                lower = *upper;
+            }
+        }
+
+        Self::from_buckets(&corrected)
+            .set_name(self.name.as_deref())
+            .set_buckets(self.buckets)
+            .set_sub_buckets(self.sub_buckets)
+    }
 }
 
-#[derive(Debug, PartialEq)]
+impl fmt::Display for HGRM {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            writeln!(f, "=== {} ===", name)?;
+        }
+
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>12} {:>12}\n",
+            "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+        )?;
+
+        for percentile in &self.percentiles {
+            writeln!(f, "{}", percentile)?;
+        }
+
+        writeln!(
+            f,
+            "#[Mean    = {:>12.3}, StdDeviation   = {:>12.3}]",
+            self.mean, self.std_deviation
+        )?;
+        writeln!(
+            f,
+            "#[Max     = {:>12.3}, Total count    = {:>12}]",
+            self.max, self.total_count
+        )?;
+        writeln!(
+            f,
+            "#[Buckets = {:>12}, SubBuckets     = {:>12}]",
+            self.buckets, self.sub_buckets
+        )
+    }
+}
+
+/// An inconsistency found by `HGRM::validate`, at a percentile row counted
+/// from 1 when it concerns one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub row: Option<usize>,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(row: Option<usize>, message: impl ToString) -> Self {
+        Self {
+            row,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.row {
+            Some(row) => write!(f, "row {}: {}", row, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct HGRMs(Vec<HGRM>);
 
 impl HGRMs {
@@ -102,13 +435,103 @@ impl HGRMs {
         self.0.iter().fold(f64::MIN, |a, b| a.max(b.max_latency()))
     }
 
-    pub fn iter(&self) -> Iter<HGRM> {
+    pub fn min_latency(&self) -> f64 {
+        self.0.iter().fold(f64::MAX, |a, b| a.min(b.min_latency()))
+    }
+
+    pub fn iter(&self) -> Iter<'_, HGRM> {
         self.0.iter()
     }
 
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    pub fn scale(self, factor: f64) -> Self {
+        self.into_iter().map(|hgrm| hgrm.scale(factor)).collect()
+    }
+
+    /// Replaces each histogram with its coordinated omission corrected
+    /// counterpart, optionally keeping the uncorrected one alongside it so
+    /// both curves can be plotted together. Corrected histograms keep their
+    /// names unless both are kept, when they're told apart by a suffix.
+    pub fn correct_coordinated_omission(
+        self,
+        expected_interval: f64,
+        keep_uncorrected: bool,
+    ) -> Self {
+        self.into_iter()
+            .flat_map(|hgrm| {
+                let corrected = hgrm.correct_coordinated_omission(expected_interval);
+
+                if !keep_uncorrected {
+                    return vec![corrected];
+                }
+
+                let name = match &hgrm.name {
+                    Some(name) => format!("{} (corrected)", name),
+                    None => "corrected".to_string(),
+                };
+
+                vec![hgrm, corrected.set_name(Some(&name))]
+            })
+            .collect()
+    }
+
+    /// Combines all histograms into a single distribution. Bucket counts are
+    /// summed and the percentiles recomputed from them, while mean and
+    /// standard deviation are pooled from each histogram's own statistics.
+    pub fn merge(&self) -> HGRM {
+        let mut buckets: Vec<(f64, u64)> = self.iter().flat_map(|h| h.bucket_counts()).collect();
+        buckets.sort_by(|(v1, _), (v2, _)| v1.partial_cmp(v2).unwrap());
+
+        let buckets = buckets
+            .into_iter()
+            .fold(vec![], |mut acc: Vec<(f64, u64)>, (v, c)| {
+                match acc.last_mut() {
+                    Some((value, count)) if *value == v => *count += c,
+                    _ => acc.push((v, c)),
+                }
+                acc
+            });
+
+        let total = self.iter().map(|h| h.total_count).sum::<u64>() as f64;
+        let weighted = |f: &dyn Fn(&HGRM) -> f64| {
+            self.iter()
+                .map(|h| h.total_count as f64 * f(h))
+                .sum::<f64>()
+                / total
+        };
+
+        let mean = weighted(&|h| h.mean);
+        let variance = weighted(&|h| h.std_deviation.powi(2) + h.mean.powi(2)) - mean.powi(2);
+
+        let merged = HGRM::from_buckets(&buckets);
+
+        merged
+            .set_mean(if total > 0.0 { mean } else { 0.0 })
+            .set_std_deviation(if total > 0.0 {
+                variance.max(0.0).sqrt()
+            } else {
+                0.0
+            })
+            .set_max(self.iter().fold(0f64, |a, h| a.max(h.max)))
+            .set_buckets(self.iter().map(|h| h.buckets).max().unwrap_or(0))
+            .set_sub_buckets(self.iter().map(|h| h.sub_buckets).max().unwrap_or(0))
+    }
+}
+
+impl fmt::Display for HGRMs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, hgrm) in self.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", hgrm)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl Index<usize> for HGRMs {
@@ -140,13 +563,22 @@ impl FromIterator<HGRM> for HGRMs {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum OnePercentile {
     Inf,
     Value(f64),
 }
 
-#[derive(Debug, PartialEq)]
+impl fmt::Display for OnePercentile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnePercentile::Inf => write!(f, "{:>12}", "inf"),
+            OnePercentile::Value(value) => write!(f, "{:>12.2}", value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Percentile {
     pub value: f64,
     pub percentile: f64,
@@ -169,3 +601,253 @@ impl Percentile {
         }
     }
 }
+
+impl fmt::Display for Percentile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>12.3} {:>12.6} {:>12} {}",
+            self.value, self.percentile, self.total_count, self.one_percentile
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_display_matches_hdrhistogram_layout() {
+        let data = include_str!("../test/data/12Krps.hgrm");
+
+        let (_, parsed) = parse(data).unwrap();
+
+        assert_eq!(parsed.to_string(), data)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = [
+            include_str!("../test/data/12Krps.hgrm"),
+            include_str!("../test/data/16Krps.hgrm"),
+        ];
+
+        let (_, parsed) = parse(&data.join("")).unwrap();
+
+        let (_, reparsed) = parse(&parsed.to_string()).unwrap();
+
+        assert_eq!(reparsed, parsed)
+    }
+
+    #[test]
+    fn test_from_buckets_reproduces_percentiles() {
+        let data = include_str!("../test/data/12Krps.hgrm");
+
+        let (_, parsed) = parse(data).unwrap();
+        let rebuilt = HGRM::from_buckets(&parsed[0].bucket_counts());
+
+        let rows =
+            |h: &HGRM| -> Vec<String> { h.percentiles.iter().map(|p| p.to_string()).collect() };
+
+        assert_eq!(rows(&rebuilt), rows(&parsed[0]));
+        assert_eq!(rebuilt.total_count, parsed[0].total_count);
+    }
+
+    #[test]
+    fn test_merge() {
+        let data = include_str!("../test/data/12Krps.hgrm");
+
+        let (_, parsed) = parse(&[data, data].join("")).unwrap();
+        let merged = parsed.merge();
+
+        assert_eq!(merged.total_count, 2 * parsed[0].total_count);
+        assert!((merged.mean - parsed[0].mean).abs() < 1e-9);
+        assert!((merged.std_deviation - parsed[0].std_deviation).abs() < 1e-9);
+
+        let values = |h: &HGRM| -> Vec<(f64, String)> {
+            h.percentiles
+                .iter()
+                .map(|p| (p.value, format!("{:.6}", p.percentile)))
+                .collect()
+        };
+
+        assert_eq!(values(&merged), values(&parsed[0]));
+    }
+
+    #[test]
+    fn test_merge_pooled_statistics() {
+        let a = HGRM::new()
+            .set_mean(2.0)
+            .set_std_deviation(1.0)
+            .set_max(3.0)
+            .set_total_count(2)
+            .add_percentile(1.0, 0.0, 1, OnePercentile::Value(1.0))
+            .add_percentile(3.0, 1.0, 2, OnePercentile::Inf);
+        let b = HGRM::new()
+            .set_mean(6.0)
+            .set_std_deviation(0.0)
+            .set_max(6.0)
+            .set_total_count(2)
+            .add_percentile(6.0, 0.0, 2, OnePercentile::Value(1.0))
+            .add_percentile(6.0, 1.0, 2, OnePercentile::Inf);
+
+        let merged = HGRMs::new(vec![a, b]).merge();
+
+        assert_eq!(merged.total_count, 4);
+        assert_eq!(merged.mean, 4.0);
+        assert_eq!(merged.std_deviation, 4.5f64.sqrt());
+        assert_eq!(merged.max, 6.0);
+        assert_eq!(merged.bucket_counts(), vec![(1.0, 1), (3.0, 1), (6.0, 2)]);
+    }
+
+    #[test]
+    fn test_correct_coordinated_omission() {
+        let hgrm = HGRM::new()
+            .set_name(Some("wrk"))
+            .set_total_count(3)
+            .add_percentile(1.0, 0.0, 1, OnePercentile::Value(1.0))
+            .add_percentile(4.0, 0.5, 2, OnePercentile::Value(2.0))
+            .add_percentile(10.0, 1.0, 3, OnePercentile::Inf);
+
+        let corrected = hgrm.correct_coordinated_omission(3.0);
+
+        assert_eq!(corrected.name, Some("wrk".to_string()));
+        assert_eq!(
+            corrected.bucket_counts(),
+            vec![(1.0, 1), (4.0, 2), (10.0, 2)]
+        );
+        assert_eq!(corrected.total_count, 5);
+        assert_eq!(corrected.max, 10.0);
+    }
+
+    #[test]
+    fn test_correct_coordinated_omission_keeps_uncorrected() {
+        let data = include_str!("../test/data/12Krps.hgrm");
+
+        let (_, parsed) = parse(data).unwrap();
+        let total_count = parsed[0].total_count;
+
+        let corrected = parsed.clone().correct_coordinated_omission(10.0, true);
+
+        assert_eq!(corrected.len(), 2);
+        assert_eq!(corrected[0].total_count, total_count);
+        assert_eq!(corrected[1].name, Some("corrected".to_string()));
+        assert!(corrected[1].total_count > total_count);
+        assert!(corrected[1].mean > corrected[0].mean);
+
+        let named = HGRMs::new(vec![parsed[0].clone().set_name(Some("wrk"))]);
+
+        let corrected = named.clone().correct_coordinated_omission(10.0, false);
+        assert_eq!(corrected.len(), 1);
+        assert_eq!(corrected[0].name, Some("wrk".to_string()));
+
+        let corrected = named.correct_coordinated_omission(10.0, true);
+        assert_eq!(corrected[0].name, Some("wrk".to_string()));
+        assert_eq!(corrected[1].name, Some("wrk (corrected)".to_string()));
+    }
+
+    #[test]
+    fn test_correct_coordinated_omission_without_interval() {
+        let data = include_str!("../test/data/12Krps.hgrm");
+
+        let (_, parsed) = parse(data).unwrap();
+
+        assert_eq!(parsed[0].correct_coordinated_omission(0.0), parsed[0]);
+    }
+
+    #[test]
+    fn test_value_at_percentile() {
+        let data = include_str!("../test/data/12Krps.hgrm");
+
+        let (_, parsed) = parse(data).unwrap();
+
+        assert_eq!(parsed[0].value_at_percentile(0.0), 0.189);
+        assert_eq!(parsed[0].value_at_percentile(0.5), 4.919);
+        assert_eq!(parsed[0].value_at_percentile(0.9), 6.591);
+        assert_eq!(parsed[0].value_at_percentile(1.0), 64.767);
+    }
+
+    #[test]
+    fn test_round_trip_named() {
+        let hgrms = HGRMs::new(vec![
+            HGRM::new()
+                .set_name(Some("Name 1"))
+                .set_mean(4.881)
+                .set_std_deviation(1.777)
+                .set_max(64.736)
+                .set_total_count(3477000)
+                .set_buckets(27)
+                .set_sub_buckets(2048)
+                .add_percentile(0.189, 0.000000, 1, OnePercentile::Value(1.00))
+                .add_percentile(4.919, 0.500000, 1740028, OnePercentile::Value(2.00))
+                .add_percentile(64.767, 1.000000, 3477000, OnePercentile::Inf),
+            HGRM::new()
+                .set_name(Some("Name 2"))
+                .set_mean(2.5)
+                .set_std_deviation(0.5)
+                .set_max(10.0)
+                .set_total_count(10)
+                .set_buckets(20)
+                .set_sub_buckets(2048)
+                .add_percentile(1.0, 0.000000, 1, OnePercentile::Value(1.00))
+                .add_percentile(10.0, 1.000000, 10, OnePercentile::Inf),
+        ]);
+
+        let text = hgrms.to_string();
+
+        assert!(text.starts_with("=== Name 1 ===\n       Value   Percentile"));
+
+        let (_, parsed) = parse(&text).unwrap();
+
+        assert_eq!(parsed, hgrms)
+    }
+
+    #[test]
+    fn test_validate() {
+        let data = [
+            include_str!("../test/data/12Krps.hgrm"),
+            include_str!("../test/data/16Krps.hgrm"),
+        ];
+        let (_, parsed) = parse(&data.join("")).unwrap();
+
+        assert!(parsed.iter().all(|hgrm| hgrm.validate().is_empty()));
+        assert!(HGRM::from_buckets(&[(1.0, 2), (3.0, 5)])
+            .validate()
+            .is_empty());
+
+        let corrupted = HGRM::new()
+            .set_max(8.0)
+            .set_total_count(10)
+            .add_percentile(1.0, 0.0, 1, OnePercentile::Value(1.0))
+            .add_percentile(4.0, 0.5, 5, OnePercentile::Value(2.0))
+            .add_percentile(3.0, 0.75, 4, OnePercentile::Value(3.0))
+            .add_percentile(5.0, 0.7, 6, OnePercentile::Inf);
+
+        let problems: Vec<String> = corrupted
+            .validate()
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect();
+
+        assert_eq!(
+            problems,
+            vec![
+                "row 3: value 3 is below the previous row's 4",
+                "row 3: total count 4 is below the previous row's 5",
+                "row 3: 1/(1-Percentile) 3 doesn't match percentile 0.75, expected 4.00",
+                "row 4: percentile 0.7 is below the previous row's 0.75",
+                "row 4: 1/(1-Percentile) is inf for percentile 0.7",
+                "row 4: total count 6 doesn't match Total count 10",
+                "row 4: value 5 doesn't match Max 8",
+            ]
+        );
+        assert_eq!(
+            HGRM::new().validate(),
+            vec![Problem::new(None, "no percentile rows")]
+        );
+    }
+}
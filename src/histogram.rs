@@ -0,0 +1,313 @@
+use plotters::coord::ranged1d::{AsRangedCoord, ValueFormatter};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::hgrm::{HGRMs, HGRM};
+use crate::renderer::{self, Chart, Facet, RenderOptions, Style};
+use crate::units::LatencyUnit;
+
+const BINS: usize = 40;
+const PANEL_WIDTH: u32 = 320;
+const PANEL_HEIGHT: u32 = 300;
+/// Opacity of bars, so overlapping series stay visible.
+const BAR_OPACITY: f64 = 0.45;
+
+/// Latency distributions as bars of the share of requests in each range.
+pub struct Histogram {
+    histograms: HGRMs,
+    facets: Vec<Facet>,
+    options: RenderOptions,
+}
+
+impl Histogram {
+    pub fn new(histograms: HGRMs, options: RenderOptions) -> Self {
+        let facets = options.facets(&histograms);
+
+        Self {
+            histograms,
+            facets,
+            options,
+        }
+    }
+
+    fn x_range(&self) -> (f64, f64) {
+        let unit = self.options.latency_unit;
+        let x_max = match self.options.y_max {
+            Some(y_max) => unit.convert(y_max),
+            None => self
+                .options
+                .slos
+                .iter()
+                .fold(unit.convert(self.histograms.max_latency()), |a, slo| {
+                    a.max(slo.threshold_in(unit))
+                }),
+        };
+        let x_min = match self.options.y_min {
+            Some(y_min) => unit.convert(y_min),
+            None if self.options.log_y => unit.convert(self.histograms.min_latency()),
+            None => 0.0,
+        };
+
+        if self.options.log_y {
+            (x_min.max(f64::EPSILON), x_max)
+        } else {
+            (x_min, x_max)
+        }
+    }
+
+    fn draw_bars<DB, X>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &Style,
+        facet: &Facet,
+        edges: &[f64],
+        y_max: f64,
+        x_spec: X,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+        X: AsRangedCoord<Value = f64>,
+        X::CoordDescType: ValueFormatter<f64>,
+    {
+        let histograms = &facet.histograms;
+        let slos = &self.options.slos;
+        let unit = self.options.latency_unit;
+        let theme = style.theme;
+        let highlight = theme.highlight();
+
+        let mut builder = ChartBuilder::on(root);
+        if let Some(name) = &facet.name {
+            builder.caption(name, style.font(16.0));
+        }
+
+        let mut chart = builder
+            .margin(style.px(5))
+            .x_label_area_size(style.px(35))
+            .y_label_area_size(style.px(60))
+            .build_cartesian_2d(x_spec, 0.0..y_max)?;
+
+        let x_range = chart.x_range();
+
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_desc(
+                self.options
+                    .x_label
+                    .clone()
+                    .unwrap_or_else(|| format!("Latency ({})", unit.symbol())),
+            )
+            .y_desc(self.options.y_label.as_deref().unwrap_or("Requests (%)"))
+            .y_max_light_lines(5)
+            .axis_style(theme.axis())
+            .bold_line_style(theme.bold_grid())
+            .light_line_style(theme.light_grid())
+            .label_style(style.font(12.0))
+            .axis_desc_style(style.font(14.0))
+            .draw()?;
+
+        for slo in slos {
+            chart.draw_series(std::iter::once(Rectangle::new(
+                [(slo.threshold_in(unit), 0.0), (x_range.end, y_max)],
+                highlight.mix(0.1).filled(),
+            )))?;
+        }
+
+        let legend_width = style.px(20) as i32;
+
+        for (idx, histogram) in histograms.iter().enumerate() {
+            let color = self.options.series_color(idx, histogram);
+            let shares = shares(histogram, edges, unit);
+
+            chart
+                .draw_series(
+                    edges
+                        .windows(2)
+                        .zip(shares)
+                        .filter(|(_, share)| *share > 0.0)
+                        .map(|(edge, share)| {
+                            Rectangle::new(
+                                [(edge[0], 0.0), (edge[1], share)],
+                                color.mix(BAR_OPACITY).filled(),
+                            )
+                        }),
+                )?
+                .label(self.options.series_name(idx, histogram))
+                .legend(move |(x, y)| {
+                    Rectangle::new(
+                        [
+                            (x, y - legend_width / 4),
+                            (x + legend_width, y + legend_width / 4),
+                        ],
+                        color.mix(BAR_OPACITY).filled(),
+                    )
+                });
+        }
+
+        for slo in slos {
+            let threshold = slo.threshold_in(unit);
+
+            chart
+                .draw_series(std::iter::once(PathElement::new(
+                    vec![(threshold, 0.0), (threshold, y_max)],
+                    highlight.mix(0.6),
+                )))?
+                .label(slo.legend(histograms, |idx, histogram| {
+                    self.options.series_name(idx, histogram)
+                }))
+                .legend(move |(x, y)| {
+                    PathElement::new(vec![(x, y), (x + legend_width, y)], highlight)
+                });
+        }
+
+        chart
+            .configure_series_labels()
+            .background_style(theme.legend_background())
+            .border_style(theme.legend_border())
+            .label_font(style.font(12.0))
+            .draw()?;
+
+        Ok(())
+    }
+}
+
+/// `BINS + 1` bin edges in the charted unit, evenly spaced or, for a log axis,
+/// evenly spaced in magnitude.
+fn edges(min: f64, max: f64, log: bool) -> Vec<f64> {
+    let max = if max > min { max } else { min + 1.0 };
+
+    (0..=BINS)
+        .map(|i| {
+            let t = i as f64 / BINS as f64;
+
+            if log {
+                min * (max / min).powf(t)
+            } else {
+                min + (max - min) * t
+            }
+        })
+        .collect()
+}
+
+/// Percentage of `histogram`'s requests falling in each bin between `edges`,
+/// requests beyond the last edge are counted in the last bin.
+fn shares(histogram: &HGRM, edges: &[f64], unit: LatencyUnit) -> Vec<f64> {
+    let bins = edges.len().saturating_sub(1);
+    let mut shares = vec![0.0; bins];
+
+    if bins == 0 || histogram.total_count == 0 {
+        return shares;
+    }
+
+    for (value, count) in histogram.bucket_counts() {
+        let value = unit.convert(value);
+        if value < edges[0] {
+            continue;
+        }
+
+        let bin = edges[1..]
+            .iter()
+            .position(|edge| value < *edge)
+            .unwrap_or(bins - 1);
+
+        shares[bin] += count as f64 * 100.0 / histogram.total_count as f64;
+    }
+
+    shares
+}
+
+impl Chart for Histogram {
+    fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    fn size(&self) -> (u32, u32) {
+        let (rows, columns) = self.options.grid(self.facets.len());
+        let height = if rows == 1 {
+            480
+        } else {
+            60 + PANEL_HEIGHT * rows as u32
+        };
+
+        (
+            self.options
+                .width
+                .unwrap_or_else(|| (PANEL_WIDTH * columns as u32).max(960)),
+            self.options.height.unwrap_or(height),
+        )
+    }
+
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &Style,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        let (x_min, x_max) = self.x_range();
+        let edges = edges(x_min, x_max, self.options.log_y);
+        // Panels share the y axis, scaled to the tallest bar of any series
+        let y_max = self
+            .histograms
+            .iter()
+            .flat_map(|histogram| shares(histogram, &edges, self.options.latency_unit))
+            .fold(0f64, f64::max)
+            .max(1.0)
+            * 1.1;
+
+        root.fill(&style.theme.background())?;
+        let root = renderer::draw_titles(root, &self.options, style)?;
+        let areas = renderer::facet_areas(&root, &self.options, self.facets.len());
+
+        for (area, facet) in areas.iter().zip(&self.facets) {
+            let (x_min, x_max) = (edges[0], edges[BINS]);
+
+            if self.options.log_y {
+                self.draw_bars(
+                    area,
+                    style,
+                    facet,
+                    &edges,
+                    y_max,
+                    (x_min..x_max).log_scale(),
+                )?;
+            } else {
+                self.draw_bars(area, style, facet, &edges, y_max, x_min..x_max)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_edges() {
+        assert_eq!(edges(0.0, 40.0, false)[..3], [0.0, 1.0, 2.0]);
+
+        let log = edges(1.0, 100.0, true);
+        assert_eq!(log.len(), BINS + 1);
+        assert!((log[BINS / 2] - 10.0).abs() < 1e-9);
+        assert!((log[BINS] - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shares() {
+        let histogram = HGRM::from_buckets(&[(500.0, 1), (1500.0, 2), (3500.0, 1), (9000.0, 4)]);
+        let edges = [0.0, 1.0, 2.0, 3.0, 4.0];
+
+        assert_eq!(
+            shares(&histogram, &edges, LatencyUnit::Ms),
+            vec![12.5, 25.0, 0.0, 62.5]
+        );
+    }
+}
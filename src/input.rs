@@ -0,0 +1,371 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use flate2::read;
+use itertools::Itertools;
+
+use crate::adapters::{self, DEFAULT_SIGNIFICANT_DIGITS};
+use crate::detect::{self, Encoding, Format};
+use crate::hgrm::HGRMs;
+use crate::parser;
+use crate::renderer;
+use crate::units::MICROS_PER_MILLI;
+
+/// Encodings removed before giving up, base64 wrapped gzip is the deepest
+/// expected.
+const MAX_ENCODINGS: usize = 4;
+/// Extensions of results files, telling a mistyped path from a payload.
+const EXTENSIONS: [&str; 8] = ["hgrm", "txt", "log", "hlog", "csv", "json", "jtl", "gz"];
+
+/// How results are read.
+#[derive(Clone, Debug)]
+pub struct InputOptions {
+    /// Precision raw samples are recorded with, in decimal digits.
+    pub significant_digits: u32,
+    /// CSV column holding raw samples, by header name or 1-based position.
+    pub column: Option<String>,
+    /// Microseconds per unit of `.hgrm` values and of raw samples without
+    /// their own unit, see `hgrm_unit` and `sample_unit` for the defaults.
+    pub unit: Option<f64>,
+    /// Format to parse, instead of detecting it from the contents.
+    pub format: Option<Format>,
+    /// Print the encodings and format detected.
+    pub verbose: bool,
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        Self {
+            significant_digits: DEFAULT_SIGNIFICANT_DIGITS,
+            column: None,
+            unit: None,
+            format: None,
+            verbose: false,
+        }
+    }
+}
+
+impl InputOptions {
+    /// Microseconds per unit of `.hgrm` values, milliseconds as wrk2 writes
+    /// them unless another unit is given.
+    pub fn hgrm_unit(&self) -> f64 {
+        self.unit.unwrap_or(MICROS_PER_MILLI)
+    }
+
+    /// Microseconds per unit of raw samples and HdrHistogram payloads,
+    /// microseconds unless another unit is given.
+    pub fn sample_unit(&self) -> f64 {
+        self.unit.unwrap_or(1.0)
+    }
+}
+
+/// Removes one layer of encoding from `data`.
+fn decode(data: &[u8], encoding: Encoding) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut decoded = vec![];
+
+    match encoding {
+        Encoding::Base64 => {
+            let compact: Vec<u8> = data
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            decoded = base64::decode(compact)?;
+        }
+        Encoding::Gzip => {
+            read::MultiGzDecoder::new(data).read_to_end(&mut decoded)?;
+        }
+        Encoding::Zlib => {
+            read::ZlibDecoder::new(data).read_to_end(&mut decoded)?;
+        }
+    }
+
+    Ok(decoded)
+}
+
+/// Removes any base64, gzip or zlib encodings wrapped around `data`,
+/// returning its text and the encodings found, outermost first.
+pub fn unwrap(mut data: Vec<u8>) -> Result<(String, Vec<Encoding>), Box<dyn std::error::Error>> {
+    let mut encodings = vec![];
+
+    while let Some(encoding) = detect::encoding(&data) {
+        if encodings.len() == MAX_ENCODINGS {
+            return Err("Too many nested encodings".into());
+        }
+
+        data = decode(&data, encoding).map_err(|e| format!("Invalid {}: {}", encoding, e))?;
+        encodings.push(encoding);
+    }
+
+    Ok((String::from_utf8(data)?, encodings))
+}
+
+/// Whether `input`, which isn't a file, was meant to be one: a single line
+/// with a path separator or a results file extension, which isn't encoded.
+fn is_path(input: &str) -> bool {
+    let extension = Path::new(input)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    !input.contains('\n')
+        && (input.contains('/')
+            || input.contains(std::path::MAIN_SEPARATOR)
+            || EXTENSIONS.contains(&extension.as_deref().unwrap_or("")))
+        && detect::encoding(input.as_bytes()).is_none()
+}
+
+/// Reads either a file or an encoded payload.
+pub fn read(input: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if Path::new(input).is_file() {
+        Ok(fs::read(input)?)
+    } else if is_path(input) {
+        Err(format!("No such file: {}", input).into())
+    } else {
+        Ok(input.as_bytes().to_vec())
+    }
+}
+
+/// The format `data` is parsed as: the one asked for, raw samples from a
+/// CSV column when one is given, or else the one detected.
+pub fn format(data: &str, options: &InputOptions) -> Format {
+    match (options.format, &options.column) {
+        (Some(format), _) => format,
+        (None, Some(_)) => Format::Csv,
+        (None, None) => detect::format(data),
+    }
+}
+
+/// Parses decoded `data` as `.hgrm` text or the results of another load
+/// testing tool, such as k6, JMeter and Gatling, or raw samples. Values are
+/// converted to microseconds.
+pub fn parse(data: &str, options: &InputOptions) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let digits = options.significant_digits;
+
+    match format(data, options) {
+        Format::Hgrm => match parser::parse(data) {
+            Ok((_, parsed)) => Ok(parsed.scale(options.hgrm_unit())),
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                let (line, column) = parser::position(data, e.input);
+
+                Err(format!("Invalid .hgrm data at line {}, column {}", line, column).into())
+            }
+            Err(e) => Err(format!("Invalid .hgrm data: {}", e).into()),
+        },
+        Format::Histf => adapters::histf(data, options.sample_unit()),
+        Format::K6 => adapters::k6(data, digits),
+        Format::Jmeter => adapters::jmeter(data, digits),
+        Format::Gatling => adapters::gatling(data, digits),
+        Format::Fortio => adapters::fortio(data, digits),
+        Format::Ghz => adapters::ghz(data, digits),
+        Format::Vegeta => adapters::vegeta(data, digits),
+        Format::Hey => adapters::hey(data, digits),
+        Format::Csv => {
+            adapters::csv_samples(data, options.column.as_deref().unwrap_or("1"), options)
+        }
+        Format::Samples => adapters::samples(data, options)
+            .map_err(|e| format!("Unrecognised input format: {}", e).into()),
+    }
+}
+
+/// Loads histograms from every results file directly in `dir`, in name
+/// order, skipping hidden files and rendered charts. Files which can't be
+/// loaded, such as a README next to the results, are skipped with a warning.
+/// Histograms without a name are named after their file.
+fn load_dir(dir: &str, options: &InputOptions) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(".");
+
+            path.is_file() && !name.starts_with('.') && !renderer::is_chart(name)
+        })
+        .collect();
+    paths.sort();
+
+    let mut hgrms = vec![];
+
+    for path in paths {
+        let stem = path.file_stem().and_then(|stem| stem.to_str());
+
+        let loaded = match load(&path.to_string_lossy(), options) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                eprintln!("{}, skipping it", e);
+                continue;
+            }
+        };
+
+        hgrms.extend(loaded.into_iter().map(|hgrm| match hgrm.name {
+            Some(_) => hgrm,
+            None => hgrm.set_name(stem),
+        }));
+    }
+
+    if hgrms.is_empty() {
+        return Err(format!("No results found in {}", dir).into());
+    }
+
+    Ok(HGRMs::new(hgrms))
+}
+
+/// Loads histograms from either a path to a results file, a directory of
+/// them or an encoded payload.
+pub fn load(input: &str, options: &InputOptions) -> Result<HGRMs, Box<dyn std::error::Error>> {
+    if Path::new(input).is_dir() {
+        return load_dir(input, options);
+    }
+
+    let label = if Path::new(input).is_file() {
+        input
+    } else {
+        "data"
+    };
+
+    let (data, encodings) =
+        unwrap(read(input)?).map_err(|e| format!("Unable to decode {}: {}", label, e))?;
+
+    if options.verbose {
+        let detected = encodings
+            .iter()
+            .map(Encoding::to_string)
+            .chain(std::iter::once(format(&data, options).to_string()))
+            .join(" > ");
+
+        eprintln!("Detected {}: {}", label, detected);
+    }
+
+    parse(&data, options).map_err(|e| format!("Unable to parse {}: {}", label, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use flate2::{write, Compression};
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    const HGRM: &str = include_str!("../test/data/12Krps.hgrm");
+
+    #[test]
+    fn test_unwrap() {
+        let mut gzip = write::GzEncoder::new(vec![], Compression::default());
+        gzip.write_all(HGRM.as_bytes()).unwrap();
+        let gzipped = gzip.finish().unwrap();
+
+        let mut zlib = write::ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(HGRM.as_bytes()).unwrap();
+        let zlibbed = zlib.finish().unwrap();
+
+        let cases = [
+            (HGRM.as_bytes().to_vec(), vec![]),
+            (gzipped.clone(), vec![Encoding::Gzip]),
+            (
+                base64::encode(&gzipped).into_bytes(),
+                vec![Encoding::Base64, Encoding::Gzip],
+            ),
+            (zlibbed, vec![Encoding::Zlib]),
+            (base64::encode(HGRM).into_bytes(), vec![Encoding::Base64]),
+        ];
+
+        for (data, encodings) in cases {
+            assert_eq!(
+                unwrap(data).unwrap(),
+                (HGRM.to_string(), encodings.clone()),
+                "{:?}",
+                encodings
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_override() {
+        let options = InputOptions {
+            format: Some(Format::Samples),
+            ..Default::default()
+        };
+
+        assert_eq!(format(HGRM, &InputOptions::default()), Format::Hgrm);
+        assert_eq!(format(HGRM, &options), Format::Samples);
+        assert!(parse(HGRM, &options).is_err());
+
+        let column = InputOptions {
+            column: Some("latency".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(format("latency\n1\n", &column), Format::Csv);
+    }
+
+    #[test]
+    fn test_hgrm_unit() {
+        let p50 = |unit| {
+            let options = InputOptions {
+                unit,
+                ..Default::default()
+            };
+
+            parse(HGRM, &options).unwrap()[0].value_at_percentile(0.5)
+        };
+
+        assert_eq!(p50(None), 4919.0);
+        assert_eq!(p50(Some(1.0)), 4.919);
+    }
+
+    #[test]
+    fn test_invalid_hgrm() {
+        let data = HGRM.replace("#[Max ", "#[Maximum ");
+        let error = parse(&data, &InputOptions::default())
+            .unwrap_err()
+            .to_string();
+
+        assert_eq!(error, "Invalid .hgrm data at line 110, column 6");
+    }
+
+    #[test]
+    fn test_truncated_histogram() {
+        let truncated: String = include_str!("../test/data/16Krps.hgrm")
+            .split_inclusive('\n')
+            .take(40)
+            .collect();
+        let data = format!("{}{}", HGRM, truncated);
+
+        let error = parse(&data, &InputOptions::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid .hgrm data at line 112, column 8"
+        );
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let options = InputOptions::default();
+
+        for input in ["nonexistent.hgrm", "results/run1", "test/data/missing"] {
+            let error = load(input, &options).unwrap_err().to_string();
+
+            assert_eq!(error, format!("No such file: {}", input));
+        }
+
+        // Payloads aren't mistaken for paths
+        assert!(load(&base64::encode(HGRM), &options).is_ok());
+        assert!(load("1000\n2000\n", &options).is_ok());
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let loaded = load("test/data", &InputOptions::default()).unwrap();
+        let names: Vec<Option<&str>> = loaded.iter().map(|hgrm| hgrm.name.as_deref()).collect();
+
+        // The README in there isn't results, and is skipped
+        assert_eq!(names, vec![Some("12Krps"), Some("16Krps")]);
+    }
+}
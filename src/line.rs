@@ -1,78 +1,692 @@
+use std::ops::RangeInclusive;
+
+use itertools::Itertools;
+use plotters::coord::ranged1d::{AsRangedCoord, ValueFormatter};
+use plotters::coord::Shift;
 use plotters::prelude::*;
 
-use crate::hgrm::HGRMs;
+use crate::hgrm::{HGRMs, HGRM};
+use crate::renderer::{self, Chart, Facet, LineStyle, RenderOptions, Style};
+use crate::runs::{self, Aggregate, Group};
+use crate::units::{check_percentile, format_percentile, DEFAULT_PERCENTILES};
+
+const DEFAULT_KEY_POINTS: [f64; 7] = [0.9999, 0.999, 0.99, 0.95, 0.9, 0.5, 0.1];
+const ANNOTATION_FONT_SIZE: i32 = 12;
+const TABLE_ROW_HEIGHT: u32 = 18;
+const PANEL_WIDTH: u32 = 320;
+const PANEL_HEIGHT: u32 = 300;
+/// Opacity of the band around aggregated runs, so the median stays visible.
+const BAND_OPACITY: f64 = 0.2;
+
+/// A percentile value label attached to the point it describes.
+struct Callout {
+    point: (i32, i32),
+    text: String,
+    color: RGBAColor,
+}
 
-pub struct Line<'a> {
+pub struct Line {
     histograms: HGRMs,
-    filename: &'a str,
+    facets: Vec<Facet>,
+    /// Runs aggregated into each series of each facet, drawn with a band.
+    aggregates: Vec<Vec<Option<Aggregate>>>,
+    options: RenderOptions,
 }
 
-impl<'a> Line<'a> {
-    pub fn new(histograms: HGRMs, filename: &'a str) -> Self {
+impl Line {
+    /// Creates a line chart, aggregating runs which share a key when
+    /// `group_runs` is set.
+    pub fn new(histograms: HGRMs, options: RenderOptions) -> Self {
+        let (histograms, aggregates): (Vec<HGRM>, Vec<Option<Aggregate>>) =
+            match &options.group_runs {
+                Some(pattern) => runs::group(&histograms, pattern, options.band)
+                    .into_iter()
+                    .map(|group| match group {
+                        Group::Single(histogram) => (histogram, None),
+                        Group::Aggregated(aggregate) => (aggregate.median.clone(), Some(aggregate)),
+                    })
+                    .unzip(),
+                None => histograms.into_iter().map(|h| (h, None)).unzip(),
+            };
+        let histograms = HGRMs::new(histograms);
+        let (facets, aggregates) = options
+            .facet_indices(&histograms)
+            .into_iter()
+            .map(|(name, indices)| {
+                let facet = Facet {
+                    name,
+                    histograms: HGRMs::new(
+                        indices.iter().map(|&i| histograms[i].clone()).collect(),
+                    ),
+                };
+
+                (
+                    facet,
+                    indices.iter().map(|&i| aggregates[i].clone()).collect(),
+                )
+            })
+            .unzip();
+
         Self {
             histograms,
-            filename,
+            facets,
+            aggregates,
+            options,
+        }
+    }
+
+    fn chart_height(&self) -> u32 {
+        let (rows, _) = self.options.grid(self.facets.len());
+
+        match self.options.height {
+            Some(height) => height,
+            None if rows == 1 => 480,
+            None => 60 + PANEL_HEIGHT * rows as u32,
+        }
+    }
+
+    fn table_height(&self) -> u32 {
+        if self.options.table {
+            TABLE_ROW_HEIGHT * (self.histograms.len() as u32 + 2)
+        } else {
+            0
+        }
+    }
+
+    /// Percentiles shown on the x axis, `max_percentile` defaults to the
+    /// highest percentile recorded below 100%. The axis is logarithmic
+    /// towards 100%, so the range and its key points have to end before it.
+    fn percentile_range(&self) -> Result<RangeInclusive<f64>, String> {
+        for key_point in self.options.key_points.iter().flatten() {
+            check_percentile(*key_point).map_err(|e| format!("Invalid key point: {}", e))?;
+        }
+
+        let recorded_max = self
+            .histograms
+            .iter()
+            .flat_map(|histogram| histogram.percentiles.iter())
+            .map(|percentile| percentile.percentile)
+            .filter(|percentile| *percentile < 1.0f64)
+            .fold(0f64, f64::max);
+
+        let min = self.options.min_percentile.unwrap_or(0.0);
+        let max = self.options.max_percentile.unwrap_or(recorded_max);
+
+        if max >= 1.0 {
+            return Err(format!(
+                "Highest percentile {} must be less than 1, which is charted at infinity",
+                max
+            ));
         }
+        if min >= max {
+            return Err(format!(
+                "Lowest percentile {} must be less than the highest, {}",
+                min, max
+            ));
+        }
+
+        Ok(min..=max)
+    }
+
+    /// Draws the titles and one panel per facet, sharing both axes.
+    fn draw_facets<DB, Y>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &Style,
+        percentile_range: RangeInclusive<f64>,
+        y_spec: Y,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+        Y: AsRangedCoord<Value = f64> + Clone,
+        Y::CoordDescType: ValueFormatter<f64>,
+    {
+        let root = renderer::draw_titles(root, &self.options, style)?;
+        let areas = renderer::facet_areas(&root, &self.options, self.facets.len());
+
+        for ((area, facet), aggregates) in areas.iter().zip(&self.facets).zip(&self.aggregates) {
+            self.draw_chart(
+                area,
+                style,
+                facet,
+                aggregates,
+                percentile_range.clone(),
+                y_spec.clone(),
+            )?;
+        }
+
+        Ok(())
     }
 
-    pub fn render(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let size = (960, 480);
-        let root = SVGBackend::new(self.filename, size).into_drawing_area();
+    fn draw_chart<DB, Y>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &Style,
+        facet: &Facet,
+        aggregates: &[Option<Aggregate>],
+        percentile_range: RangeInclusive<f64>,
+        y_spec: Y,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+        Y: AsRangedCoord<Value = f64>,
+        Y::CoordDescType: ValueFormatter<f64>,
+    {
+        let histograms = &facet.histograms;
+        let slos = &self.options.slos;
+        let unit = self.options.latency_unit;
+        let theme = style.theme;
+        let highlight = theme.highlight();
+        let (x_min, x_max) = (*percentile_range.start(), *percentile_range.end());
+        let key_points = match &self.options.key_points {
+            Some(key_points) => key_points.clone(),
+            None => DEFAULT_KEY_POINTS.to_vec(),
+        };
+        let key_points = key_points
+            .into_iter()
+            .filter(|key_point| percentile_range.contains(key_point))
+            .collect();
 
-        let mut chart = ChartBuilder::on(&root)
-            .caption("Latency", ("sans-serif", 30).into_font())
-            .margin(5)
-            .x_label_area_size(35)
-            .y_label_area_size(60)
+        let mut callouts = vec![];
+
+        let mut builder = ChartBuilder::on(root);
+        if let Some(name) = &facet.name {
+            builder.caption(name, style.font(16.0));
+        }
+
+        let mut chart = builder
+            .margin(style.px(5))
+            .x_label_area_size(style.px(35))
+            .y_label_area_size(style.px(60))
             .build_cartesian_2d(
-                (0f64..1f64)
+                (x_min..self.options.max_percentile.unwrap_or(1f64))
                     .log_scale()
                     .zero_point(1.0)
-                    .with_key_points(vec![0.9999, 0.999, 0.99, 0.95, 0.9, 0.5, 0.1]),
-                0f64..(self.histograms.max_latency() / 1000f64),
+                    .with_key_points(key_points),
+                y_spec,
             )?;
 
+        let y_range = chart.y_range();
+        let (y_min, y_max) = (y_range.start, y_range.end);
+
         chart
             .configure_mesh()
-            .x_desc("Percentile")
+            .x_desc(self.options.x_label.as_deref().unwrap_or("Percentile"))
             .x_label_formatter(&|x| format!("{}%", *x * 100.0))
-            .y_desc("Milliseconds")
+            .y_desc(
+                self.options
+                    .y_label
+                    .as_deref()
+                    .unwrap_or(unit.description()),
+            )
             .y_max_light_lines(5)
+            .axis_style(theme.axis())
+            .bold_line_style(theme.bold_grid())
+            .light_line_style(theme.light_grid())
+            .label_style(style.font(12.0))
+            .axis_desc_style(style.font(14.0))
             .draw()?;
 
-        for (idx, histogram) in self.histograms.iter().enumerate() {
-            let color = Palette99::pick(idx);
+        for slo in slos
+            .iter()
+            .filter(|slo| percentile_range.contains(&slo.percentile))
+        {
+            let threshold = slo.threshold_in(unit);
 
-            let mut data = histogram
+            chart.draw_series(std::iter::once(Rectangle::new(
+                [(slo.percentile, threshold), (x_max, y_max)],
+                highlight.mix(0.1).filled(),
+            )))?;
+        }
+
+        for (idx, histogram) in histograms.iter().enumerate() {
+            let color = self.options.series_color(idx, histogram);
+            let line_style = color.stroke_width(style.stroke_width());
+
+            let data: Vec<(f64, f64)> = histogram
                 .percentiles
                 .iter()
                 .filter(|percentile| percentile.percentile < 1.0f64)
-                .map(|percentile| (percentile.percentile, percentile.value / 1000f64));
+                .filter(|percentile| percentile_range.contains(&percentile.percentile))
+                .map(|percentile| (percentile.percentile, unit.convert(percentile.value)))
+                .collect();
 
-            let label = match &histogram.name {
-                Some(filename) => {
+            let aggregate = aggregates[idx].as_ref();
+            let label = match (self.options.series_label(histogram), aggregate) {
+                (Some(filename), Some(aggregate)) => {
+                    format!(
+                        "{}, median of {} runs, {} Total",
+                        filename, aggregate.runs, histogram.total_count
+                    )
+                }
+                (Some(filename), None) => {
                     format!("{}, {} Total", filename, histogram.total_count)
                 }
-                None => {
+                (None, _) => {
                     format!("{} Total", histogram.total_count)
                 }
             };
 
+            if let Some(aggregate) = aggregate {
+                let band: Vec<(f64, f64, f64)> = aggregate
+                    .band
+                    .iter()
+                    .filter(|(percentile, _, _)| percentile_range.contains(percentile))
+                    .copied()
+                    .collect();
+
+                chart.draw_series(std::iter::once(Polygon::new(
+                    band.iter()
+                        .map(|(percentile, _, high)| (*percentile, unit.convert(*high)))
+                        .chain(
+                            band.iter()
+                                .rev()
+                                .map(|(percentile, low, _)| (*percentile, unit.convert(*low))),
+                        )
+                        .collect::<Vec<_>>(),
+                    color.mix(BAND_OPACITY).filled(),
+                )))?;
+            }
+
+            let series = match self.options.line_style(histogram) {
+                LineStyle::Solid => chart.draw_series(LineSeries::new(data, line_style))?,
+                LineStyle::Dashed => chart.draw_series(DashedLineSeries::new(
+                    data.iter().copied(),
+                    style.px(6),
+                    style.px(4),
+                    line_style,
+                ))?,
+                LineStyle::Dotted => chart.draw_series(DashedLineSeries::new(
+                    data.iter().copied(),
+                    style.px(1),
+                    style.px(3),
+                    line_style,
+                ))?,
+            };
+
+            let legend_width = style.px(20) as i32;
+            series.label(label).legend(move |(x, y)| {
+                PathElement::new(vec![(x, y), (x + legend_width, y)], line_style)
+            });
+
+            chart.draw_series(slos.iter().map(|slo| {
+                let value = unit.convert(histogram.value_at_percentile(slo.percentile));
+                let marker = if slo.is_violated_by(histogram) {
+                    highlight.filled()
+                } else {
+                    color.filled()
+                };
+
+                Circle::new((slo.percentile, value), style.px(4), marker)
+            }))?;
+
+            for percentile in self
+                .options
+                .annotate
+                .iter()
+                .filter(|p| percentile_range.contains(p))
+            {
+                let value = histogram.value_at_percentile(*percentile);
+                let point = (*percentile, unit.convert(value));
+
+                chart.draw_series(std::iter::once(Circle::new(
+                    point,
+                    style.px(3),
+                    color.filled(),
+                )))?;
+
+                callouts.push(Callout {
+                    point: chart.backend_coord(&point),
+                    text: format!("{} {}", format_percentile(*percentile), unit.format(value)),
+                    color: color.to_rgba(),
+                });
+            }
+        }
+
+        let legend_width = style.px(20) as i32;
+        for slo in slos {
+            let threshold = slo.threshold_in(unit);
+
             chart
-                .draw_series(LineSeries::new(&mut data, &color))?
-                .label(label)
+                .draw_series(std::iter::once(PathElement::new(
+                    vec![(x_min, threshold), (x_max, threshold)],
+                    highlight.mix(0.6),
+                )))?
+                .label(slo.legend(histograms, |idx, histogram| {
+                    self.options.series_name(idx, histogram)
+                }))
                 .legend(move |(x, y)| {
-                    let color = Palette99::pick(idx);
-                    PathElement::new(vec![(x, y), (x + 20, y)], color)
+                    PathElement::new(vec![(x, y), (x + legend_width, y)], highlight)
                 });
+
+            chart.draw_series(std::iter::once(PathElement::new(
+                vec![(slo.percentile, y_min), (slo.percentile, y_max)],
+                highlight.mix(0.6),
+            )))?;
+
+            chart.draw_series(std::iter::once(Cross::new(
+                (slo.percentile, threshold),
+                style.px(6),
+                highlight.stroke_width(style.px(2)),
+            )))?;
         }
 
         chart
             .configure_series_labels()
-            .background_style(&WHITE.mix(0.8))
-            .border_style(&BLACK)
+            .background_style(theme.legend_background())
+            .border_style(theme.legend_border())
+            .label_font(style.font(12.0))
             .draw()?;
 
+        let plot_area = chart.plotting_area().get_pixel_range();
+        draw_callouts(root, style, callouts, (plot_area.1.start, plot_area.1.end))?;
+
         Ok(())
     }
+
+    fn draw_table<DB: DrawingBackend>(
+        &self,
+        area: &DrawingArea<DB, Shift>,
+        style: &Style,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB::ErrorType: 'static,
+    {
+        let percentiles: Vec<f64> = if self.options.annotate.is_empty() {
+            DEFAULT_PERCENTILES.to_vec()
+        } else {
+            self.options.annotate.clone()
+        };
+
+        let font = style.font(ANNOTATION_FONT_SIZE as f64);
+        let (width, _) = area.dim_in_pixel();
+        let margin = style.px(10) as i32;
+        let name_width = style.px(300) as i32;
+        let column_width =
+            (width as i32 - name_width - 2 * margin) / (percentiles.len() as i32 + 1);
+        let row_y =
+            |row: usize| style.px(TABLE_ROW_HEIGHT) as i32 * row as i32 + style.px(4) as i32;
+        let column_x = |column: usize| name_width + margin + column_width * column as i32;
+
+        area.draw(&Text::new("Series", (margin, row_y(0)), font.clone()))?;
+        area.draw(&Text::new("Total", (column_x(0), row_y(0)), font.clone()))?;
+        for (column, percentile) in percentiles.iter().enumerate() {
+            area.draw(&Text::new(
+                format_percentile(*percentile),
+                (column_x(column + 1), row_y(0)),
+                font.clone(),
+            ))?;
+        }
+
+        let rows = self
+            .facets
+            .iter()
+            .flat_map(|facet| facet.histograms.iter().enumerate());
+
+        for (row, (idx, histogram)) in rows.enumerate() {
+            let row = row + 1;
+            let color = self.options.series_color(idx, histogram);
+            let font = font.color(&color);
+            let name = self.options.series_name(idx, histogram);
+
+            area.draw(&Text::new(name, (margin, row_y(row)), font.clone()))?;
+            area.draw(&Text::new(
+                histogram.total_count.to_string(),
+                (column_x(0), row_y(row)),
+                font.clone(),
+            ))?;
+            for (column, percentile) in percentiles.iter().enumerate() {
+                area.draw(&Text::new(
+                    self.options
+                        .latency_unit
+                        .format(histogram.value_at_percentile(*percentile)),
+                    (column_x(column + 1), row_y(row)),
+                    font.clone(),
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Chart for Line {
+    fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    fn size(&self) -> (u32, u32) {
+        let (_, columns) = self.options.grid(self.facets.len());
+
+        (
+            self.options
+                .width
+                .unwrap_or_else(|| (PANEL_WIDTH * columns as u32).max(960)),
+            self.chart_height() + self.table_height(),
+        )
+    }
+
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &Style,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        root.fill(&style.theme.background())?;
+        let (root, table_area) = root.split_vertically(style.px(self.chart_height()));
+
+        let percentile_range = self.percentile_range()?;
+        let unit = self.options.latency_unit;
+        let y_max = match self.options.y_max {
+            Some(y_max) => unit.convert(y_max),
+            None => self
+                .options
+                .slos
+                .iter()
+                .fold(unit.convert(self.histograms.max_latency()), |a, slo| {
+                    a.max(slo.threshold_in(unit))
+                }),
+        };
+
+        if self.options.log_y {
+            let y_min = match self.options.y_min {
+                Some(y_min) => unit.convert(y_min),
+                None => unit.convert(self.histograms.min_latency()),
+            };
+
+            self.draw_facets(
+                &root,
+                style,
+                percentile_range,
+                (y_min.max(f64::EPSILON)..y_max).log_scale(),
+            )
+        } else {
+            let y_min = unit.convert(self.options.y_min.unwrap_or(0.0));
+
+            self.draw_facets(&root, style, percentile_range, y_min..y_max)
+        }?;
+
+        if self.options.table {
+            self.draw_table(&table_area, style)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Draws each callout next to its point, pushing labels that share a column
+/// apart vertically so overlapping series stay readable.
+fn draw_callouts<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    style: &Style,
+    mut callouts: Vec<Callout>,
+    bounds: (i32, i32),
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let font_size = style.font_size(ANNOTATION_FONT_SIZE as f64);
+    let line_height = font_size.round() as i32 + style.px(2) as i32;
+    let char_width = (font_size * 0.6).round() as i32;
+    let offset = style.px(10) as i32;
+    let (width, _) = area.dim_in_pixel();
+    let (base_x, base_y) = area.get_base_pixel();
+    let bounds = (bounds.0 - base_y, bounds.1 - base_y);
+
+    for callout in callouts.iter_mut() {
+        callout.point = (callout.point.0 - base_x, callout.point.1 - base_y);
+    }
+
+    callouts.sort_by_key(|callout| (callout.point.0, callout.point.1));
+
+    for (_, group) in &callouts.iter().group_by(|callout| callout.point.0) {
+        let group: Vec<&Callout> = group.collect();
+        let positions = spread(
+            group.iter().map(|callout| callout.point.1).collect(),
+            line_height,
+            bounds,
+        );
+
+        for (callout, y) in group.iter().zip(positions) {
+            let (x, point_y) = callout.point;
+            let text_width = callout.text.len() as i32 * char_width;
+            let label_x = if x + offset + text_width > width as i32 {
+                x - offset - text_width
+            } else {
+                x + offset
+            };
+            let anchor_x = if label_x < x { x - offset } else { x + offset };
+
+            area.draw(&PathElement::new(
+                vec![(x, point_y), (anchor_x, y)],
+                callout.color,
+            ))?;
+            area.draw(&Text::new(
+                callout.text.clone(),
+                (label_x, y - line_height / 2),
+                style
+                    .font(ANNOTATION_FONT_SIZE as f64)
+                    .color(&callout.color),
+            ))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Spreads sorted label positions so neighbours are at least `gap` apart,
+/// keeping them within `bounds` where possible.
+fn spread(positions: Vec<i32>, gap: i32, bounds: (i32, i32)) -> Vec<i32> {
+    let mut spread: Vec<i32> = vec![];
+
+    for position in positions {
+        let position = match spread.last() {
+            Some(prev) => position.max(prev + gap),
+            None => position.max(bounds.0 + gap / 2),
+        };
+        spread.push(position);
+    }
+
+    let overflow = spread.last().map_or(0, |last| last - (bounds.1 - gap / 2));
+    if overflow > 0 {
+        let mut limit = bounds.1 - gap / 2;
+        for position in spread.iter_mut().rev() {
+            *position = (*position).min(limit);
+            limit = *position - gap;
+        }
+    }
+
+    spread
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_percentile_range() {
+        let data = include_str!("../test/data/12Krps.hgrm");
+        let (_, parsed) = parse(data).unwrap();
+
+        let line = |min_percentile, max_percentile| {
+            Line::new(
+                parsed.clone(),
+                RenderOptions {
+                    min_percentile,
+                    max_percentile,
+                    ..RenderOptions::default()
+                },
+            )
+        };
+        let with_key_points = |key_points: Vec<f64>| {
+            Line::new(
+                parsed.clone(),
+                RenderOptions {
+                    key_points: Some(key_points),
+                    ..RenderOptions::default()
+                },
+            )
+            .percentile_range()
+        };
+
+        let range = line(None, None).percentile_range().unwrap();
+        assert_eq!(range, 0.0..=0.999999);
+        assert!(range.contains(&0.999999));
+
+        assert_eq!(
+            line(Some(0.9), Some(0.999)).percentile_range(),
+            Ok(0.9..=0.999)
+        );
+        assert!(line(None, Some(1.0)).percentile_range().is_err());
+        assert!(line(Some(0.99), Some(0.9)).percentile_range().is_err());
+        assert!(line(Some(0.99), Some(0.99)).percentile_range().is_err());
+
+        assert!(with_key_points(vec![0.999, 0.5]).is_ok());
+        assert!(with_key_points(vec![3.0, -1.0]).is_err());
+        assert!(with_key_points(vec![0.5, 1.0]).is_err());
+    }
+
+    #[test]
+    fn test_group_runs() {
+        let data = include_str!("../test/data/12Krps.hgrm");
+        let (_, parsed) = parse(data).unwrap();
+        let run = |name| parsed[0].clone().set_name(Some(name));
+
+        let line = Line::new(
+            HGRMs::new(vec![run("a#run1"), run("b"), run("a#run2")]),
+            RenderOptions {
+                group_runs: Some(regex::Regex::new("^(.+)#run").unwrap()),
+                facet_by: Some(regex::Regex::new("^(a|b)").unwrap()),
+                ..RenderOptions::default()
+            },
+        );
+
+        let runs: Vec<Vec<Option<usize>>> = line
+            .aggregates
+            .iter()
+            .map(|facet| facet.iter().map(|a| a.as_ref().map(|a| a.runs)).collect())
+            .collect();
+        assert_eq!(runs, vec![vec![Some(2)], vec![None]]);
+        assert_eq!(line.facets[1].histograms[0].name.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_spread() {
+        assert_eq!(spread(vec![100, 150], 14, (0, 480)), vec![100, 150]);
+        assert_eq!(
+            spread(vec![100, 100, 105], 14, (0, 480)),
+            vec![100, 114, 128]
+        );
+        assert_eq!(spread(vec![470, 475], 14, (0, 480)), vec![459, 473]);
+        assert_eq!(spread(vec![0], 14, (0, 480)), vec![7]);
+    }
 }
@@ -1,33 +1,484 @@
+mod adapters;
+mod batch;
+mod compare;
+mod config;
+mod density;
+mod detect;
 mod hgrm;
+mod histogram;
+mod input;
 mod line;
 mod parser;
 mod renderer;
+mod report;
+mod runs;
+mod serve;
+mod slo;
+mod theme;
+mod units;
 mod violin;
+mod watch;
 
-use std::io::Read;
+use std::fs;
+use std::io::{self, Write};
 
-use clap::Parser;
-use flate2::read;
-use parser::parse;
-use renderer::{Renderer, RendererInput};
+use clap::{Parser, Subcommand};
+use compare::CompareOptions;
+use config::Config;
+use density::{Bandwidth, Smoothing};
+use detect::Format;
+use hgrm::{HGRMs, HGRM};
+use input::InputOptions;
+use regex::Regex;
+use renderer::{ImageFormat, RenderOptions, Renderer, RendererInput};
+use report::{IndexFormat, ReportOptions};
+use runs::Band;
+use slo::Slo;
+use theme::Theme;
+use units::LatencyUnit;
 
 #[macro_use]
 extern crate self_update;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     #[clap(short, long)]
     update: bool,
 
     #[clap(required_unless_present = "update")]
-    data: String,
+    data: Option<String>,
+
+    /// File to render to, or - for stdout
+    #[clap(
+        short,
+        long,
+        short_alias = 'o',
+        alias = "output",
+        required_unless_present = "update"
+    )]
+    filename: Option<String>,
+
+    /// Image format of the chart [default: from the file extension, or svg for stdout]
+    #[clap(arg_enum, long)]
+    format: Option<ImageFormat>,
+
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    render: RenderArgs,
+
+    /// Correct for coordinated omission given the expected interval between requests, e.g. 100us
+    #[clap(long, value_parser = units::parse_positive_duration)]
+    expected_interval: Option<f64>,
+
+    /// Plot the uncorrected histograms alongside the corrected ones
+    #[clap(long, requires = "expected-interval")]
+    show_uncorrected: bool,
+
+    /// Re-render whenever the input file, or any file in the input directory, changes
+    #[clap(short, long)]
+    watch: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Merge histograms from multiple inputs into a single distribution
+    Merge(Box<MergeArgs>),
+    /// Run a local HTTP server rendering histograms POSTed to it, with options in the query string
+    Serve(ServeArgs),
+    /// Render every chart listed in a TOML, JSON or YAML manifest, in parallel
+    Batch(BatchArgs),
+    /// Render line, violin and histogram charts with percentile tables for each group of inputs, indexed in HTML or Markdown
+    Report(Box<ReportArgs>),
+    /// Test whether a candidate's latency differs significantly from a baseline's
+    Compare(Box<CompareArgs>),
+    /// Check histograms for inconsistencies left by corrupted or truncated files
+    Validate(ValidateArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct MergeArgs {
+    /// Paths to .hgrm files, directories of them or encoded histogram payloads
+    #[clap(required = true)]
+    inputs: Vec<String>,
+
+    /// Name given to the merged histogram
+    #[clap(short, long)]
+    name: Option<String>,
+
+    /// Write the merged histogram as .hgrm text, or - for stdout, printed to stdout when neither this nor --filename is set
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// Render the merged histogram to this file, or - for stdout
+    #[clap(short, long)]
+    filename: Option<String>,
+
+    /// Image format of the chart [default: from the file extension, or svg for stdout]
+    #[clap(arg_enum, long)]
+    format: Option<ImageFormat>,
+
+    /// Merge again whenever one of the inputs, or any file in an input directory, changes
+    #[clap(short, long)]
+    watch: bool,
+
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct BatchArgs {
+    /// Manifest listing the inputs, output and options of each chart
+    manifest: String,
+
+    /// Number of charts rendered at once [default: one per CPU]
+    #[clap(short, long)]
+    jobs: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReportArgs {
+    /// Paths to .hgrm files, directories of them or encoded histogram payloads, each a group unless --group-by is set
+    #[clap(required = true)]
+    inputs: Vec<String>,
+
+    /// Directory the index and charts are written to
+    #[clap(short, long, default_value = "report")]
+    output: String,
+
+    /// Format of the index
+    #[clap(arg_enum, long, default_value = "html")]
+    index: IndexFormat,
+
+    /// Format of the charts
+    #[clap(arg_enum, long, default_value = "svg")]
+    format: ImageFormat,
+
+    /// Charts rendered for each group, e.g. line,histogram [default: all]
+    #[clap(arg_enum, long, value_delimiter = ',')]
+    views: Vec<RendererInput>,
+
+    /// Group series across inputs by this pattern on their names, using the first capture group when there is one, e.g. '^[^@]+'
+    #[clap(long, value_parser = Regex::new)]
+    group_by: Option<Regex>,
+
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// Histograms of the baseline, merged when there's more than one
+    baseline: String,
+
+    /// Histograms of the candidate, merged when there's more than one
+    candidate: String,
+
+    /// Percentiles given bootstrap confidence intervals, e.g. 0.5,0.99
+    #[clap(short, long, value_delimiter = ',', default_values_t = units::DEFAULT_PERCENTILES)]
+    percentiles: Vec<f64>,
+
+    /// Confidence level of the intervals, and one minus the significance level of the tests
+    #[clap(long, default_value_t = 0.95)]
+    confidence: f64,
+
+    /// Number of bootstrap resamples
+    #[clap(long, default_value_t = 1000)]
+    iterations: usize,
 
-    #[clap(short, long, required_unless_present = "update")]
+    /// Also run a Mann–Whitney U test
+    #[clap(long)]
+    mann_whitney: bool,
+
+    /// Seed of the bootstrap
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Render both distributions, annotated with the outcome, to this file, or - for stdout
+    #[clap(short, long)]
     filename: Option<String>,
 
-    #[clap(arg_enum, short, long, default_value_t = RendererInput::Line)]
-    renderer: RendererInput,
+    /// Image format of the chart [default: from the file extension, or svg for stdout]
+    #[clap(arg_enum, long)]
+    format: Option<ImageFormat>,
+
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct ValidateArgs {
+    /// Paths to .hgrm files, directories of them or encoded histogram payloads
+    #[clap(required = true)]
+    inputs: Vec<String>,
+
+    #[clap(flatten)]
+    input: InputArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct InputArgs {
+    /// Precision of histograms recorded from raw samples, in decimal digits
+    #[clap(long, default_value_t = adapters::DEFAULT_SIGNIFICANT_DIGITS, value_parser = clap::value_parser!(u32).range(1..=5))]
+    significant_digits: u32,
+
+    /// Read raw samples from this CSV column, by header name or 1-based position
+    #[clap(long)]
+    column: Option<String>,
+
+    /// Unit of .hgrm values and of raw samples given without one, e.g. us [default: ms for .hgrm, us otherwise]
+    #[clap(long, value_parser = units::parse_unit)]
+    unit: Option<f64>,
+
+    /// Format of the input, instead of detecting it from the contents
+    #[clap(arg_enum, long)]
+    input_format: Option<Format>,
+
+    /// Print the encodings and format detected for each input
+    #[clap(short, long)]
+    verbose: bool,
+}
+
+impl InputArgs {
+    fn options(&self) -> InputOptions {
+        InputOptions {
+            significant_digits: self.significant_digits,
+            column: self.column.clone(),
+            unit: self.unit,
+            format: self.input_format,
+            verbose: self.verbose,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct RenderArgs {
+    /// Renderer to use [default: line]
+    #[clap(arg_enum, short, long)]
+    renderer: Option<RendererInput>,
+
+    /// TOML or YAML file with rendering options, overridden by any flags given
+    #[clap(long)]
+    config: Option<String>,
+
+    /// Service level objective to overlay as <percentile>:<duration>, e.g. 0.99:50ms (repeatable)
+    #[clap(long = "slo", value_parser)]
+    slos: Vec<Slo>,
+
+    /// Label these percentiles on each line series, e.g. 0.99,0.999
+    #[clap(long, value_delimiter = ',', value_parser = units::parse_percentile)]
+    annotate: Vec<f64>,
+
+    /// Add a table of percentile values per series below the line chart
+    #[clap(long, overrides_with = "no-table")]
+    table: bool,
+
+    /// Leave out the table, even when the config file adds it
+    #[clap(long, overrides_with = "table")]
+    no_table: bool,
+
+    /// Percentiles to mark on the line chart's x axis, e.g. 0.99999,0.9999,0.999
+    #[clap(long, value_delimiter = ',')]
+    key_points: Option<Vec<f64>>,
+
+    /// Lowest percentile shown on the line chart
+    #[clap(long)]
+    min_percentile: Option<f64>,
+
+    /// Highest percentile shown on the line chart, e.g. 0.999999
+    #[clap(long)]
+    max_percentile: Option<f64>,
+
+    /// Lower bound of the latency axis, e.g. 1ms
+    #[clap(long, value_parser = units::parse_duration)]
+    y_min: Option<f64>,
+
+    /// Upper bound of the latency axis, e.g. 50ms
+    #[clap(long, value_parser = units::parse_duration)]
+    y_max: Option<f64>,
+
+    /// Unit latencies are charted and tabulated in [default: ms]
+    #[clap(arg_enum, long)]
+    latency_unit: Option<LatencyUnit>,
+
+    /// Use a logarithmic latency axis
+    #[clap(long, overrides_with = "no-log-y")]
+    log_y: bool,
+
+    /// Use a linear latency axis, even when the config file asks for a logarithmic one
+    #[clap(long, overrides_with = "log-y")]
+    no_log_y: bool,
+
+    /// Draw pairs of histograms on either side of a single violin to compare them
+    #[clap(long, overrides_with = "no-split")]
+    split: bool,
+
+    /// Draw each histogram as its own violin, even when the config file splits them
+    #[clap(long, overrides_with = "split")]
+    no_split: bool,
+
+    /// How violin shapes are estimated [default: spline]
+    #[clap(arg_enum, long)]
+    smoothing: Option<Smoothing>,
+
+    /// Smoothing parameter of the violin spline, from 0 (straight line) to 1 (no smoothing) [default: 0.99]
+    #[clap(long)]
+    smoothing_factor: Option<f64>,
+
+    /// Kernel bandwidth when --smoothing=kde: silverman, scott or a duration, e.g. 50us [default: silverman]
+    #[clap(long, value_parser)]
+    bandwidth: Option<Bandwidth>,
+
+    /// Clip violin shapes at zero where smoothing overshoots [default: on]
+    #[clap(long, overrides_with = "no-clamp")]
+    clamp: bool,
+
+    /// Draw violin shapes as smoothed, even where they overshoot below zero
+    #[clap(long, overrides_with = "clamp")]
+    no_clamp: bool,
+
+    /// Colour theme [default: light]
+    #[clap(arg_enum, long)]
+    theme: Option<Theme>,
+
+    /// Chart title [default: Latency]
+    #[clap(long)]
+    title: Option<String>,
+
+    /// Line shown below the title, e.g. the test date and commit
+    #[clap(long)]
+    subtitle: Option<String>,
+
+    /// Description of the x axis
+    #[clap(long)]
+    x_label: Option<String>,
+
+    /// Description of the y axis
+    #[clap(long)]
+    y_label: Option<String>,
+
+    /// Output width in pixels
+    #[clap(long)]
+    width: Option<u32>,
+
+    /// Output height in pixels
+    #[clap(long)]
+    height: Option<u32>,
+
+    /// Resolution of bitmap output such as .png, scaling the output size [default: 96]
+    #[clap(long)]
+    dpi: Option<u32>,
+
+    /// Font family used for all text, e.g. serif or "DejaVu Sans"
+    #[clap(long)]
+    font_family: Option<String>,
+
+    /// Base font size in pixels [default: 12]
+    #[clap(long)]
+    font_size: Option<f64>,
+
+    /// Draw a grid of panels grouping series by this pattern on their names, using the first capture group when there is one, e.g. '^[^@]+'
+    #[clap(long, value_parser = Regex::new)]
+    facet_by: Option<Regex>,
+
+    /// Number of columns in the grid of panels [default: as square as possible]
+    #[clap(long)]
+    columns: Option<usize>,
+
+    /// Draw runs whose names share a key matched by this pattern as their median line with a band, using the first capture group when there is one, e.g. '^(.+)#run'
+    #[clap(long, value_parser = Regex::new)]
+    group_runs: Option<Regex>,
+
+    /// Spread of grouped runs shaded around their median [default: min-max]
+    #[clap(arg_enum, long)]
+    band: Option<Band>,
+}
+
+impl RenderArgs {
+    /// Resolves the renderer and its options from the config file, if any,
+    /// with flags given on the command line taking precedence.
+    fn resolve(&self) -> Result<(RendererInput, RenderOptions), Box<dyn std::error::Error>> {
+        let config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+
+        let renderer = self
+            .renderer
+            .clone()
+            .or(config.renderer)
+            .unwrap_or(RendererInput::Line);
+        let mut options = config.options;
+
+        if !self.slos.is_empty() {
+            options.slos = self.slos.clone();
+        }
+        if !self.annotate.is_empty() {
+            options.annotate = self.annotate.clone();
+        }
+        options.table = switch(self.table, self.no_table).unwrap_or(options.table);
+        options.log_y = switch(self.log_y, self.no_log_y).unwrap_or(options.log_y);
+        options.split = switch(self.split, self.no_split).unwrap_or(options.split);
+        options.smoothing = self.smoothing.unwrap_or(options.smoothing);
+        options.smoothing_factor = self.smoothing_factor.or(options.smoothing_factor);
+        options.bandwidth = self.bandwidth.or(options.bandwidth);
+        options.clamp = switch(self.clamp, self.no_clamp).or(options.clamp);
+        if self.key_points.is_some() {
+            options.key_points = self.key_points.clone();
+        }
+        options.min_percentile = self.min_percentile.or(options.min_percentile);
+        options.max_percentile = self.max_percentile.or(options.max_percentile);
+        options.y_min = self.y_min.or(options.y_min);
+        options.y_max = self.y_max.or(options.y_max);
+        options.latency_unit = self.latency_unit.unwrap_or(options.latency_unit);
+        options.theme = self.theme.unwrap_or(options.theme);
+        options.title = self.title.clone().or(options.title);
+        options.subtitle = self.subtitle.clone().or(options.subtitle);
+        options.x_label = self.x_label.clone().or(options.x_label);
+        options.y_label = self.y_label.clone().or(options.y_label);
+        options.width = self.width.or(options.width);
+        options.height = self.height.or(options.height);
+        options.dpi = self.dpi.or(options.dpi);
+        options.font_family = self.font_family.clone().or(options.font_family);
+        options.font_size = self.font_size.or(options.font_size);
+        options.facet_by = self.facet_by.clone().or(options.facet_by);
+        options.columns = self.columns.or(options.columns);
+        options.group_runs = self.group_runs.clone().or(options.group_runs);
+        options.band = self.band.unwrap_or(options.band);
+
+        Ok((renderer, options))
+    }
+}
+
+/// Value of a flag given with its `--no-…` counterpart, when either is.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
 }
 
 fn update() -> Result<(), Box<dyn std::error::Error>> {
@@ -45,6 +496,232 @@ fn update() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Output path meaning stdout.
+const STDOUT: &str = "-";
+
+/// Renders to `filename`, or to stdout when it's `-`, as `format` when given
+/// and otherwise as the file extension suggests, SVG for stdout.
+fn write_chart(
+    renderer: &Renderer,
+    filename: &str,
+    format: Option<ImageFormat>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match (filename, format) {
+        (STDOUT, format) => {
+            io::stdout().write_all(&renderer.to_bytes(format.unwrap_or(ImageFormat::Svg))?)?;
+            return Ok(());
+        }
+        (_, Some(format)) => fs::write(filename, renderer.to_bytes(format)?)?,
+        (_, None) => renderer.render(filename)?,
+    }
+
+    println!("Generated {}", filename);
+
+    Ok(())
+}
+
+fn merge(args: &MergeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let chart_to_stdout = args.filename.as_deref() == Some(STDOUT);
+
+    if chart_to_stdout && args.output.as_deref() == Some(STDOUT) {
+        return Err("Only one of --output and --filename can write to stdout".into());
+    }
+
+    let options = args.input.options();
+    let mut hgrms = vec![];
+
+    for input in &args.inputs {
+        hgrms.extend(input::load(input, &options)?);
+    }
+
+    let merged = HGRMs::new(hgrms).merge().set_name(args.name.as_deref());
+    // Written in the unit .hgrm inputs are read in, so it can be read back
+    let text = merged.clone().scale(1.0 / options.hgrm_unit()).to_string();
+
+    match args.output.as_deref() {
+        Some(STDOUT) => print!("{}", text),
+        Some(output) => {
+            fs::write(output, &text)?;
+
+            if chart_to_stdout {
+                eprintln!("Wrote {}", output);
+            } else {
+                println!("Wrote {}", output);
+            }
+        }
+        None => (),
+    }
+
+    match &args.filename {
+        Some(filename) => {
+            let (renderer, options) = args.render.resolve()?;
+            let renderer = Renderer::new(renderer, HGRMs::new(vec![merged]), options);
+
+            write_chart(&renderer, filename, args.format)?;
+        }
+        None if args.output.is_none() => print!("{}", text),
+        None => (),
+    }
+
+    Ok(())
+}
+
+/// Prints the problems found in each histogram of each input, failing when
+/// there are any.
+fn validate(args: &ValidateArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut checked, mut failed, mut unreadable) = (0, 0, 0);
+
+    for input in &args.inputs {
+        let histograms = match input::load(input, &args.input.options()) {
+            Ok(histograms) => histograms,
+            Err(e) => {
+                println!("FAILED {}: {}", input, e);
+                unreadable += 1;
+                continue;
+            }
+        };
+
+        for (idx, histogram) in histograms.iter().enumerate() {
+            let label = match (&histogram.name, histograms.len()) {
+                (Some(name), _) => format!("{} ({})", input, name),
+                (None, 1) => input.clone(),
+                (None, _) => format!("{} (#{})", input, idx + 1),
+            };
+            let problems = histogram.validate();
+
+            checked += 1;
+
+            if problems.is_empty() {
+                println!("ok     {}", label);
+            } else {
+                failed += 1;
+                println!("FAILED {}", label);
+
+                for problem in problems {
+                    println!("       {}", problem);
+                }
+            }
+        }
+    }
+
+    println!("\n{} of {} histograms passed", checked - failed, checked);
+
+    if unreadable > 0 {
+        println!("{} inputs couldn't be read", unreadable);
+    }
+
+    if failed + unreadable > 0 {
+        return Err(format!(
+            "{} histograms failed validation, {} inputs couldn't be read",
+            failed, unreadable
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Loads one side of a comparison as a single histogram, named after its
+/// histogram or file when it has one.
+fn load_side(input: &str, options: &InputOptions) -> Result<HGRM, Box<dyn std::error::Error>> {
+    let histograms = input::load(input, options)?;
+
+    let name = match histograms.iter().next() {
+        Some(histogram) if histograms.len() == 1 && histogram.name.is_some() => {
+            histogram.name.clone()
+        }
+        _ => std::path::Path::new(input)
+            .file_stem()
+            .filter(|_| std::path::Path::new(input).exists())
+            .map(|stem| stem.to_string_lossy().into_owned()),
+    };
+
+    Ok(histograms.merge().set_name(name.as_deref()))
+}
+
+fn compare(args: &CompareArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let options = args.input.options();
+    let mut baseline = load_side(&args.baseline, &options)?;
+    let mut candidate = load_side(&args.candidate, &options)?;
+
+    // Falls back to the roles when names can't tell the two apart
+    if baseline.name.is_none() || candidate.name.is_none() || baseline.name == candidate.name {
+        baseline = baseline.set_name(Some("baseline"));
+        candidate = candidate.set_name(Some("candidate"));
+    }
+
+    let (renderer, mut render_options) = args.render.resolve()?;
+    let comparison = compare::compare(
+        &baseline,
+        &candidate,
+        &CompareOptions {
+            percentiles: args.percentiles.clone(),
+            confidence: args.confidence,
+            iterations: args.iterations,
+            mann_whitney: args.mann_whitney,
+            seed: args.seed,
+            unit: render_options.latency_unit,
+        },
+    )?;
+
+    let baseline_name = baseline.name.clone().unwrap_or_default();
+    let candidate_name = candidate.name.clone().unwrap_or_default();
+    let summary = format!(
+        "Comparing {} ({} requests) with {} ({} requests) at {}% confidence\n\n{}\n\n{}",
+        baseline_name,
+        baseline.total_count,
+        candidate_name,
+        candidate.total_count,
+        args.confidence * 100.0,
+        comparison.tests().join("\n"),
+        comparison.table(&baseline_name, &candidate_name)
+    );
+
+    let Some(filename) = &args.filename else {
+        print!("{}", summary);
+        return Ok(());
+    };
+
+    if filename == STDOUT {
+        eprint!("{}", summary);
+    } else {
+        println!("{}", summary);
+    }
+
+    if render_options.annotate.is_empty() {
+        // The maximum, percentile 1, can't be annotated on the line chart
+        render_options.annotate = args
+            .percentiles
+            .iter()
+            .copied()
+            .filter(|p| *p < 1.0)
+            .collect();
+    }
+    render_options.notes = comparison.tests();
+    render_options.notes.extend(comparison.differences());
+
+    let renderer = Renderer::new(
+        renderer,
+        HGRMs::new(vec![baseline, candidate]),
+        render_options,
+    );
+
+    write_chart(&renderer, filename, args.format)
+}
+
+fn plot(args: &Args, data: &str, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let parsed = input::load(data, &args.input.options())?;
+    let parsed = match args.expected_interval {
+        Some(interval) => parsed.correct_coordinated_omission(interval, args.show_uncorrected),
+        None => parsed,
+    };
+
+    let (renderer, options) = args.render.resolve()?;
+    let renderer = Renderer::new(renderer, parsed, options);
+
+    write_chart(&renderer, filename, args.format)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
@@ -52,27 +729,55 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return update();
     };
 
-    println!("Decoding...");
-
-    let compressed = base64::decode(args.data)?;
+    if let Some(command) = &args.command {
+        return match command {
+            Command::Merge(merge_args) if merge_args.watch => {
+                let outputs: Vec<&str> = [&merge_args.output, &merge_args.filename]
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect();
 
-    println!("Uncompressing...");
+                watch::watch(&merge_args.inputs, &outputs, || merge(merge_args))
+            }
+            Command::Merge(merge_args) => merge(merge_args),
+            Command::Serve(serve_args) => serve::serve(&serve_args.listen),
+            Command::Batch(batch_args) => batch::batch(&batch_args.manifest, batch_args.jobs),
+            Command::Compare(compare_args) => compare(compare_args),
+            Command::Validate(validate_args) => validate(validate_args),
+            Command::Report(report_args) => {
+                let (_, options) = report_args.render.resolve()?;
+                let index = report::report(
+                    &report_args.inputs,
+                    &report_args.input.options(),
+                    &options,
+                    &ReportOptions {
+                        output: report_args.output.clone(),
+                        index: report_args.index,
+                        image: report_args.format,
+                        views: report_args.views.clone(),
+                        group_by: report_args.group_by.clone(),
+                    },
+                )?;
 
-    let mut gz = read::GzDecoder::new(&compressed[..]);
-    let mut data = String::new();
-    gz.read_to_string(&mut data)?;
-
-    if let Ok((_, parsed)) = parse(&data) {
-        let filename = &args.filename.unwrap();
-        let renderer = Renderer::new(args.renderer, parsed, filename);
+                println!("Generated {}", index);
+                Ok(())
+            }
+        };
+    }
 
-        renderer.render()?;
+    let data = args.data.clone().unwrap();
+    let filename = args.filename.clone().unwrap();
 
-        println!("Generated {}", filename);
+    if filename != STDOUT {
+        println!("Decoding...");
+    }
 
-        Ok(())
-    } else {
-        println!("Unable to parse data!");
-        Ok(())
+    if args.watch {
+        return watch::watch(std::slice::from_ref(&data), &[&filename], || {
+            plot(&args, &data, &filename)
+        });
     }
+
+    plot(&args, &data, &filename)
 }
@@ -1,41 +1,51 @@
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until1},
-    character::complete::{digit1, line_ending, multispace0},
-    combinator::{map_res, opt, recognize},
-    error::ParseError,
-    multi::{many0, many1, separated_list1},
-    sequence::{delimited, preceded, separated_pair, tuple},
+    bytes::complete::{tag, tag_no_case, take_until1},
+    character::complete::{char, multispace0, multispace1, not_line_ending, space0, space1},
+    combinator::{map, map_res, not, opt, recognize},
+    error::{Error, ErrorKind, ParseError},
+    multi::{many0, many1},
+    number::complete::recognize_float,
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
     IResult,
 };
 
 use crate::hgrm::{HGRMs, OnePercentile, Percentile, HGRM};
 
+/// A number as printed by HdrHistogram or other tools, e.g. `4.919`, `12`,
+/// `-0.0` or `1.5e-3`.
 fn decimal_float(s: &str) -> IResult<&str, f64> {
-    map_res(recognize(tuple((digit1, tag("."), digit1))), |s: &str| {
-        s.parse::<f64>()
-    })(s)
+    map_res(recognize_float, |s: &str| s.parse::<f64>())(s)
 }
 
+/// A count, which some tools print as a float, e.g. `3477000` or `3.477e6`.
 fn digit_u64(s: &str) -> IResult<&str, u64> {
-    map_res(digit1, |s: &str| s.parse::<u64>())(s)
+    map_res(recognize_float, |s: &str| match s.parse::<u64>() {
+        Ok(count) => Ok(count),
+        Err(e) => match s.parse::<f64>() {
+            Ok(count) if count >= 0.0 && count.fract() == 0.0 => Ok(count as u64),
+            _ => Err(e),
+        },
+    })(s)
 }
 
 fn one_percentile(s: &str) -> IResult<&str, OnePercentile> {
-    map_res(
-        alt((recognize(tuple((digit1, tag("."), digit1))), tag("inf"))),
-        |s: &str| match s {
-            "inf" => Ok(OnePercentile::Inf),
-            decimal_str => decimal_str.parse::<f64>().map(OnePercentile::Value),
-        },
-    )(s)
+    alt((
+        map(alt((tag_no_case("infinity"), tag_no_case("inf"))), |_| {
+            OnePercentile::Inf
+        }),
+        map(decimal_float, OnePercentile::Value),
+    ))(s)
 }
 
 fn percentile_line(s: &str) -> IResult<&str, Percentile> {
-    let (rest, (value, percentile, total_count, one_percentile)) = tuple((
-        ws(decimal_float),
-        ws(decimal_float),
-        ws(digit_u64),
+    let (rest, (value, _, percentile, _, total_count, _, one_percentile)) = tuple((
+        preceded(space0, decimal_float),
+        space1,
+        decimal_float,
+        space1,
+        digit_u64,
+        space1,
         one_percentile,
     ))(s)?;
 
@@ -43,39 +53,49 @@ fn percentile_line(s: &str) -> IResult<&str, Percentile> {
     Ok((rest, percentile))
 }
 
-fn ws<'a, F: 'a, O, E: ParseError<&'a str>>(
-    inner: F,
-) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
+fn ws<'a, F, O, E: ParseError<&'a str>>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
 where
-    F: Fn(&'a str) -> IResult<&'a str, O, E>,
+    F: Fn(&'a str) -> IResult<&'a str, O, E> + 'a,
 {
     delimited(multispace0, inner, multispace0)
 }
 
-fn aggregate<'a, F1: 'a, F2: 'a, F3: 'a, F4: 'a, G1, G2, O1, O2, E: ParseError<&'a str> + 'a>(
+/// A comment line such as `# generated by wrk2`, which unlike the `#[`
+/// footer lines carries nothing.
+fn comment(s: &str) -> IResult<&str, &str> {
+    recognize(tuple((char('#'), not(char('[')), not_line_ending)))(s)
+}
+
+/// Whitespace, including line endings of either kind, and comment lines
+/// between the sections of a histogram.
+fn gap(s: &str) -> IResult<&str, ()> {
+    map(many0(alt((multispace1, comment))), |_| ())(s)
+}
+
+fn aggregate<'a, F1, F2, F3, F4, G1, G2, O1, O2, E: ParseError<&'a str> + 'a>(
     left_title: F1,
     left: F2,
     right_title: F3,
     right: F4,
 ) -> impl FnMut(&'a str) -> IResult<&'a str, (O1, O2), E>
 where
-    F1: Fn(&'a str) -> IResult<&'a str, G1, E>,
-    F2: Fn(&'a str) -> IResult<&'a str, O1, E>,
-    F3: Fn(&'a str) -> IResult<&'a str, G2, E>,
-    F4: Fn(&'a str) -> IResult<&'a str, O2, E>,
+    F1: Fn(&'a str) -> IResult<&'a str, G1, E> + 'a,
+    F2: Fn(&'a str) -> IResult<&'a str, O1, E> + 'a,
+    F3: Fn(&'a str) -> IResult<&'a str, G2, E> + 'a,
+    F4: Fn(&'a str) -> IResult<&'a str, O2, E> + 'a,
 {
     delimited(
         tag("#["),
         separated_pair(
-            preceded(tuple((left_title, ws(tag("=")))), left),
+            preceded(tuple((ws(left_title), ws(tag("=")))), left),
             ws(tag(",")),
             preceded(tuple((right_title, ws(tag("=")))), right),
         ),
-        tag("]"),
+        ws(tag("]")),
     )
 }
 
-fn parse_name<'a>(s: &'a str) -> IResult<&str, Option<&'a str>> {
+fn parse_name(s: &str) -> IResult<&str, Option<&str>> {
     let (rest, res1) = opt(preceded(multispace0, many1(tag("="))))(s)?;
     match res1 {
         None => Ok((s, None)),
@@ -96,36 +116,49 @@ fn parse_name<'a>(s: &'a str) -> IResult<&str, Option<&'a str>> {
 }
 
 fn parse_hgrm(s: &str) -> IResult<&str, HGRM> {
-    let (rest, name) = parse_name(s)?;
+    let (rest, _) = gap(s)?;
+    let (rest, name) = parse_name(rest)?;
+    let (rest, _) = gap(rest)?;
 
     let (rest, _) = tuple((
-        ws(tag("Value")),
-        ws(tag("Percentile")),
-        ws(tag("TotalCount")),
-        tag("1/(1-Percentile)"),
+        ws(tag_no_case("Value")),
+        ws(tag_no_case("Percentile")),
+        ws(tag_no_case("TotalCount")),
+        tag_no_case("1/(1-Percentile)"),
     ))(rest)?;
-    let (rest, _) = many0(line_ending)(rest)?;
+    let (rest, _) = gap(rest)?;
 
-    let (rest, percentiles) = separated_list1(line_ending, percentile_line)(rest)?;
+    let (rest, percentiles) = many1(terminated(percentile_line, gap))(rest)?;
 
-    let (rest, _) = many1(line_ending)(rest)?;
-
-    let (rest, (mean, std_deviation)) = aggregate(
-        tag("Mean"),
-        decimal_float,
-        tag("StdDeviation"),
-        decimal_float,
+    let (rest, (mean, std_deviation)) = terminated(
+        aggregate(
+            tag_no_case("Mean"),
+            decimal_float,
+            tag_no_case("StdDeviation"),
+            decimal_float,
+        ),
+        gap,
     )(rest)?;
 
-    let (rest, _) = many1(line_ending)(rest)?;
-
-    let (rest, (max, total_count)) =
-        aggregate(tag("Max"), decimal_float, tag("Total count"), digit_u64)(rest)?;
-
-    let (rest, _) = many1(line_ending)(rest)?;
+    let (rest, (max, total_count)) = terminated(
+        aggregate(
+            tag_no_case("Max"),
+            decimal_float,
+            tag_no_case("Total count"),
+            digit_u64,
+        ),
+        gap,
+    )(rest)?;
 
-    let (rest, (buckets, sub_buckets)) =
-        aggregate(tag("Buckets"), digit_u64, tag("SubBuckets"), digit_u64)(rest)?;
+    let (rest, (buckets, sub_buckets)) = terminated(
+        aggregate(
+            tag_no_case("Buckets"),
+            digit_u64,
+            tag_no_case("SubBuckets"),
+            digit_u64,
+        ),
+        gap,
+    )(rest)?;
 
     let hgrm = HGRM::new()
         .set_name(name)
@@ -140,8 +173,57 @@ fn parse_hgrm(s: &str) -> IResult<&str, HGRM> {
     Ok((rest, hgrm))
 }
 
+/// Whether `line` is the header of a histogram's percentile rows.
+pub fn is_header(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+
+    matches!(
+        (words.next(), words.next()),
+        (Some(value), Some(percentile))
+            if value.eq_ignore_ascii_case("value")
+                && percentile.eq_ignore_ascii_case("percentile")
+    )
+}
+
+/// Skips anything before the first histogram's name or header, such as the
+/// summary wrk2 prints ahead of its detailed percentile spectrum.
+fn skip_preamble(s: &str) -> &str {
+    let mut offset = 0;
+
+    for line in s.split_inclusive('\n') {
+        if is_header(line) || line.trim_start().starts_with("===") {
+            return &s[offset..];
+        }
+        offset += line.len();
+    }
+
+    s
+}
+
+/// Line and column, both counted from 1, at which `rest`, a suffix of `s`
+/// left by a parser, starts.
+pub fn position(s: &str, rest: &str) -> (usize, usize) {
+    let consumed = &s[..s.len() - rest.len()];
+    let line_start = consumed.rfind('\n').map_or(0, |idx| idx + 1);
+
+    (
+        consumed.matches('\n').count() + 1,
+        consumed[line_start..].chars().count() + 1,
+    )
+}
+
+/// Whether `rest`, left after the last histogram, is nothing but the
+/// summary wrk2 prints after its detailed percentile spectrum.
+fn is_postamble(rest: &str) -> bool {
+    rest.is_empty() || rest.starts_with("---")
+}
+
 pub fn parse(s: &str) -> IResult<&str, HGRMs> {
-    let (rest, hgrms) = many1(parse_hgrm)(s)?;
+    let (rest, hgrms) = many1(parse_hgrm)(skip_preamble(s))?;
+
+    if !is_postamble(rest) {
+        return Err(nom::Err::Error(Error::new(rest, ErrorKind::Eof)));
+    }
 
     Ok((rest, HGRMs::new(hgrms)))
 }
@@ -235,4 +317,155 @@ mod tests {
 
         assert_eq!(parsed, HGRMs::new(vec![expected]))
     }
+
+    const SMALL: &str = "       Value   Percentile   TotalCount 1/(1-Percentile)
+
+       0.189     0.000000            1         1.00
+       4.919     0.500000            5         2.00
+      64.767     1.000000           10          inf
+#[Mean    =        4.881, StdDeviation   =        1.777]
+#[Max     =       64.736, Total count    =           10]
+#[Buckets =           27, SubBuckets     =         2048]
+";
+
+    fn small() -> HGRMs {
+        HGRMs::new(vec![HGRM::new()
+            .set_mean(4.881)
+            .set_std_deviation(1.777)
+            .set_max(64.736)
+            .set_total_count(10)
+            .set_buckets(27)
+            .set_sub_buckets(2048)
+            .add_percentile(0.189, 0.0, 1, OnePercentile::Value(1.0))
+            .add_percentile(4.919, 0.5, 5, OnePercentile::Value(2.0))
+            .add_percentile(64.767, 1.0, 10, OnePercentile::Inf)])
+    }
+
+    #[test]
+    fn test_scientific_notation() {
+        assert_eq!(decimal_float("1e-3"), Ok(("", 0.001)));
+        assert_eq!(decimal_float("+2.5E+2"), Ok(("", 250.0)));
+        assert_eq!(digit_u64("3.477e6"), Ok(("", 3477000)));
+        assert!(digit_u64("1.5").is_err());
+
+        let data = SMALL
+            .replace("0.189     0.000000", "1.89e-1     0e0")
+            .replace("4.919     0.500000", "4.919E0     5.0E-1")
+            .replace("2.00", "2e0")
+            .replace("4.881", "4.881e+0");
+
+        assert_eq!(parse(&data).unwrap().1, small());
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(decimal_float("12"), Ok(("", 12.0)));
+
+        let data = SMALL
+            .replace("0.000000", "0")
+            .replace("1.000000", "1")
+            .replace("1.00\n", "1\n");
+
+        assert_eq!(parse(&data).unwrap().1, small());
+    }
+
+    #[test]
+    fn test_negative_zero() {
+        assert_eq!(decimal_float("-0.0"), Ok(("", 0.0)));
+
+        let data = SMALL.replace("0.189     0.000000", "0.189    -0.000000");
+        let (_, parsed) = parse(&data).unwrap();
+
+        assert_eq!(parsed[0].percentiles[0].percentile, 0.0);
+    }
+
+    #[test]
+    fn test_crlf() {
+        let data = SMALL.replace('\n', "\r\n");
+
+        assert_eq!(parse(&data).unwrap().1, small());
+    }
+
+    #[test]
+    fn test_trailing_whitespace() {
+        let data = SMALL.replace('\n', " \t \n");
+
+        assert_eq!(parse(&data).unwrap().1, small());
+        assert_eq!(parse(&data.replace('\n', "\r\n")).unwrap().1, small());
+    }
+
+    #[test]
+    fn test_blank_and_comment_lines() {
+        let data = format!(
+            "# wrk2 latency distribution\n\n{}",
+            SMALL
+                .replace("\n#[Mean", "\n\n# summary\n#[Mean")
+                .replace("\n#[Max", "\n\n#[Max")
+                .replace("       4.919", "\n# median\n       4.919")
+        );
+
+        assert_eq!(parse(&data).unwrap().1, small());
+
+        let (_, parsed) = parse(&format!("{}\n# next run\n\n{}", data, SMALL)).unwrap();
+        assert_eq!(parsed.len(), 2);
+    }
+
+    #[test]
+    fn test_header_case() {
+        let data = SMALL.replace(
+            "Value   Percentile   TotalCount 1/(1-Percentile)",
+            "value\tpercentile totalCount   1/(1-percentile)",
+        );
+
+        assert_eq!(parse(&data).unwrap().1, small());
+    }
+
+    #[test]
+    fn test_wrk2_output() {
+        let data = format!(
+            "Running 30s test @ http://127.0.0.1:8080/
+  2 threads and 100 connections
+  Thread calibration: mean lat.: 1.329ms, rate sampling interval: 10ms
+  Thread Stats   Avg      Stdev     Max   +/- Stdev
+    Latency     4.88ms    1.78ms  64.74ms   71.02%
+    Req/Sec     5.28k   401.12     7.89k    70.03%
+  Latency Distribution (HdrHistogram - Recorded Latency)
+ 50.000%    4.92ms
+ 100.000%   64.77ms
+
+  Detailed Percentile spectrum:
+{}----------------------------------------------------------
+  10 requests in 30.00s, 1.20KB read
+Requests/sec:      0.33
+",
+            SMALL
+        );
+
+        assert_eq!(parse(&data).unwrap().1, small());
+    }
+
+    #[test]
+    fn test_trailing_input() {
+        let data = format!("{}# done\n\n", SMALL);
+        assert_eq!(parse(&data).unwrap().1, small());
+
+        let data = format!("{}\n  oops\n", SMALL);
+        let err = parse(&data).unwrap_err();
+        assert_eq!(
+            err,
+            nom::Err::Error(Error::new(
+                &data[data.find("oops").unwrap()..],
+                ErrorKind::Eof
+            ))
+        );
+    }
+
+    #[test]
+    fn test_position() {
+        let data = "Value Percentile\n\n  0.189 oops\n";
+        let rest = &data[data.find("oops").unwrap()..];
+
+        assert_eq!(position(data, rest), (3, 9));
+        assert_eq!(position(data, data), (1, 1));
+    }
 }
@@ -1,30 +1,589 @@
-use crate::hgrm::HGRMs;
+use std::path::Path;
+
+use image::codecs::png::PngEncoder;
+use image::{ColorType, ImageEncoder};
+use itertools::Itertools;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::FontFamily;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config;
+use crate::density::{Bandwidth, Smoothing};
+use crate::hgrm::{HGRMs, HGRM};
+use crate::histogram;
 use crate::line;
+use crate::runs::Band;
+use crate::slo::Slo;
+use crate::theme::Theme;
+use crate::units::LatencyUnit;
 use crate::violin;
 
-#[derive(clap::ArgEnum, Clone, Debug)]
+/// Options shared by all renderers.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RenderOptions {
+    #[serde(rename = "slo", deserialize_with = "config::slos")]
+    pub slos: Vec<Slo>,
+    #[serde(deserialize_with = "config::percentiles")]
+    pub annotate: Vec<f64>,
+    pub table: bool,
+    pub key_points: Option<Vec<f64>>,
+    pub min_percentile: Option<f64>,
+    pub max_percentile: Option<f64>,
+    #[serde(deserialize_with = "config::duration")]
+    pub y_min: Option<f64>,
+    #[serde(deserialize_with = "config::duration")]
+    pub y_max: Option<f64>,
+    pub latency_unit: LatencyUnit,
+    pub log_y: bool,
+    pub split: bool,
+    pub smoothing: Smoothing,
+    pub smoothing_factor: Option<f64>,
+    #[serde(deserialize_with = "config::bandwidth")]
+    pub bandwidth: Option<Bandwidth>,
+    /// Clip violin densities at zero, on unless set.
+    pub clamp: Option<bool>,
+    pub theme: Theme,
+    pub series: Vec<SeriesOptions>,
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    /// Lines of text drawn under the titles, such as the outcome of a
+    /// comparison.
+    #[serde(skip)]
+    pub notes: Vec<String>,
+    pub x_label: Option<String>,
+    pub y_label: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub dpi: Option<u32>,
+    pub font_family: Option<String>,
+    pub font_size: Option<f64>,
+    #[serde(deserialize_with = "config::regex")]
+    pub facet_by: Option<Regex>,
+    pub columns: Option<usize>,
+    #[serde(deserialize_with = "config::regex")]
+    pub group_runs: Option<Regex>,
+    pub band: Band,
+}
+
+impl RenderOptions {
+    /// Orders histograms as listed in `series`, followed by any unlisted ones
+    /// in their original order, dropping hidden series.
+    pub fn arrange(&self, histograms: HGRMs) -> HGRMs {
+        let position = |hgrm: &HGRM| {
+            self.series
+                .iter()
+                .position(|series| Some(&series.name) == hgrm.name.as_ref())
+                .unwrap_or(self.series.len())
+        };
+
+        let mut histograms: Vec<HGRM> = histograms
+            .into_iter()
+            .filter(|hgrm| !self.series_options(hgrm).is_some_and(|s| s.hidden))
+            .collect();
+        histograms.sort_by_key(|hgrm| position(hgrm));
+
+        HGRMs::new(histograms)
+    }
+
+    pub fn series_options(&self, hgrm: &HGRM) -> Option<&SeriesOptions> {
+        let name = hgrm.name.as_ref()?;
+
+        self.series.iter().find(|series| &series.name == name)
+    }
+
+    /// Display name of a histogram, if it has one.
+    pub fn series_label(&self, hgrm: &HGRM) -> Option<String> {
+        self.series_options(hgrm)
+            .and_then(|series| series.label.clone())
+            .or_else(|| hgrm.name.clone())
+    }
+
+    /// Display name of a histogram, falling back to its position.
+    pub fn series_name(&self, idx: usize, hgrm: &HGRM) -> String {
+        self.series_label(hgrm)
+            .unwrap_or_else(|| format!("#{}", idx + 1))
+    }
+
+    pub fn series_color(&self, idx: usize, hgrm: &HGRM) -> RGBColor {
+        self.series_options(hgrm)
+            .and_then(|series| series.color)
+            .unwrap_or_else(|| self.theme.series_color(idx))
+    }
+
+    pub fn line_style(&self, hgrm: &HGRM) -> LineStyle {
+        self.series_options(hgrm)
+            .and_then(|series| series.line_style)
+            .unwrap_or_default()
+    }
+
+    /// Facet a histogram is drawn in, either given explicitly in `series` or
+    /// taken from its name by `facet_by`, using the first capture group when
+    /// the pattern has one.
+    pub fn facet_key(&self, hgrm: &HGRM) -> Option<String> {
+        if let Some(facet) = self.series_options(hgrm).and_then(|s| s.facet.clone()) {
+            return Some(facet);
+        }
+
+        let captures = self.facet_by.as_ref()?.captures(hgrm.name.as_ref()?)?;
+        let key = captures.get(1).or_else(|| captures.get(0))?;
+
+        Some(key.as_str().to_string())
+    }
+
+    /// Groups histograms into facets in order of first appearance, histograms
+    /// without a facet key are collected under "Other". Returns a single
+    /// unnamed facet when nothing is faceted.
+    pub fn facets(&self, histograms: &HGRMs) -> Vec<Facet> {
+        self.facet_indices(histograms)
+            .into_iter()
+            .map(|(name, indices)| Facet {
+                name,
+                histograms: HGRMs::new(indices.iter().map(|&i| histograms[i].clone()).collect()),
+            })
+            .collect()
+    }
+
+    /// Names of the facets `facets` returns, with the indices of the
+    /// histograms drawn in each.
+    pub fn facet_indices(&self, histograms: &HGRMs) -> Vec<(Option<String>, Vec<usize>)> {
+        let keys: Vec<Option<String>> =
+            histograms.iter().map(|hgrm| self.facet_key(hgrm)).collect();
+
+        if keys.iter().all(Option::is_none) {
+            return vec![(None, (0..histograms.len()).collect())];
+        }
+
+        let keys: Vec<String> = keys
+            .into_iter()
+            .map(|key| key.unwrap_or_else(|| "Other".to_string()))
+            .collect();
+
+        keys.iter()
+            .unique()
+            .map(|name| {
+                let indices = (0..keys.len()).filter(|&i| &keys[i] == name).collect();
+
+                (Some(name.clone()), indices)
+            })
+            .collect()
+    }
+
+    /// Rows and columns of the grid holding `panels` facets, as square as
+    /// possible unless `columns` is set.
+    pub fn grid(&self, panels: usize) -> (usize, usize) {
+        let panels = panels.max(1);
+        let columns = self
+            .columns
+            .unwrap_or_else(|| (panels as f64).sqrt().ceil() as usize)
+            .clamp(1, panels);
+
+        (panels.div_ceil(columns), columns)
+    }
+
+    pub fn style(&self, scale: f64) -> Style {
+        Style {
+            theme: self.theme,
+            font_family: self
+                .font_family
+                .clone()
+                .unwrap_or_else(|| "sans-serif".to_string()),
+            font_scale: self.font_size.unwrap_or(DEFAULT_FONT_SIZE) / DEFAULT_FONT_SIZE * scale,
+            scale,
+        }
+    }
+}
+
+const DEFAULT_FONT_SIZE: f64 = 12.0;
+const DEFAULT_DPI: f64 = 96.0;
+
+/// Theme, fonts and pixel scale resolved for a single output.
+pub struct Style {
+    pub theme: Theme,
+    font_family: String,
+    font_scale: f64,
+    scale: f64,
+}
+
+impl Style {
+    /// Font of `size` pixels at the default font size, scaled to the
+    /// configured font size and output resolution.
+    pub fn font(&self, size: f64) -> TextStyle<'_> {
+        FontDesc::new(
+            FontFamily::from(self.font_family.as_str()),
+            self.font_size(size),
+            FontStyle::Normal,
+        )
+        .color(&self.theme.text())
+    }
+
+    pub fn font_size(&self, size: f64) -> f64 {
+        size * self.font_scale
+    }
+
+    pub fn px(&self, size: u32) -> u32 {
+        (size as f64 * self.scale).round() as u32
+    }
+
+    pub fn stroke_width(&self) -> u32 {
+        self.px(self.theme.stroke_width()).max(1)
+    }
+}
+
+/// A chart which can be drawn onto any plotters backend.
+pub trait Chart {
+    fn options(&self) -> &RenderOptions;
+
+    /// Size of the output in pixels at 96 DPI.
+    fn size(&self) -> (u32, u32);
+
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &Style,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static;
+}
+
+/// Formats charts can be rendered to in memory.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Svg,
+    Png,
+}
+
+/// Style for bitmaps, scaled from the 96 DPI charts are sized at.
+fn bitmap_style(options: &RenderOptions) -> Style {
+    options.style(options.dpi.map_or(1.0, |dpi| dpi as f64 / DEFAULT_DPI))
+}
+
+/// Renders `chart` to `filename`, as a bitmap for image extensions such as
+/// `.png` and as SVG otherwise.
+pub fn render_file<C: Chart>(chart: &C, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (width, height) = chart.size();
+    let options = chart.options();
+
+    if is_bitmap(filename) {
+        let style = bitmap_style(options);
+        let root =
+            BitMapBackend::new(filename, (style.px(width), style.px(height))).into_drawing_area();
+
+        chart.draw(&root, &style)?;
+        root.present()?;
+    } else {
+        let style = options.style(1.0);
+        let root = SVGBackend::new(filename, (width, height)).into_drawing_area();
+
+        chart.draw(&root, &style)?;
+        root.present()?;
+    }
+
+    Ok(())
+}
+
+/// Renders `chart` to an SVG document in memory.
+pub fn render_svg<C: Chart>(chart: &C) -> Result<String, Box<dyn std::error::Error>> {
+    let (width, height) = chart.size();
+    let style = chart.options().style(1.0);
+    let mut svg = String::new();
+
+    {
+        let root = SVGBackend::with_string(&mut svg, (width, height)).into_drawing_area();
+
+        chart.draw(&root, &style)?;
+        root.present()?;
+    }
+
+    Ok(svg)
+}
+
+/// Renders `chart` to a PNG image in memory.
+pub fn render_png<C: Chart>(chart: &C) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let (width, height) = chart.size();
+    let style = bitmap_style(chart.options());
+    let (width, height) = (style.px(width), style.px(height));
+    let mut pixels = vec![0; width as usize * height as usize * 3];
+
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (width, height)).into_drawing_area();
+
+        chart.draw(&root, &style)?;
+        root.present()?;
+    }
+
+    let mut png = vec![];
+    PngEncoder::new(&mut png).write_image(&pixels, width, height, ColorType::Rgb8)?;
+
+    Ok(png)
+}
+
+/// A group of histograms drawn together in one panel of a grid.
+pub struct Facet {
+    pub name: Option<String>,
+    pub histograms: HGRMs,
+}
+
+/// Splits `root` into an evenly sized grid with one area per facet.
+pub fn facet_areas<DB>(
+    root: &DrawingArea<DB, Shift>,
+    options: &RenderOptions,
+    facets: usize,
+) -> Vec<DrawingArea<DB, Shift>>
+where
+    DB: DrawingBackend,
+{
+    let (rows, columns) = options.grid(facets);
+    let mut areas = root.split_evenly((rows, columns));
+    areas.truncate(facets);
+
+    areas
+}
+
+/// Draws the title, optional subtitle and notes, returning the area below
+/// them.
+pub fn draw_titles<DB>(
+    root: &DrawingArea<DB, Shift>,
+    options: &RenderOptions,
+    style: &Style,
+) -> Result<DrawingArea<DB, Shift>, Box<dyn std::error::Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let title = options.title.as_deref().unwrap_or("Latency");
+    let root = root.titled(title, style.font(30.0))?;
+
+    let mut root = match &options.subtitle {
+        Some(subtitle) => root.titled(subtitle, style.font(16.0))?,
+        None => root,
+    };
+
+    for note in &options.notes {
+        root = root.titled(note, style.font(13.0))?;
+    }
+
+    Ok(root)
+}
+
+/// Whether `filename` looks like a rendered chart rather than results.
+pub fn is_chart(filename: &str) -> bool {
+    is_bitmap(filename) || filename.to_ascii_lowercase().ends_with(".svg")
+}
+
+fn is_bitmap(filename: &str) -> bool {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    matches!(
+        extension.as_deref(),
+        Some("png" | "jpg" | "jpeg" | "bmp" | "gif" | "tiff")
+    )
+}
+
+/// Per series overrides, matched against `HGRM::name`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SeriesOptions {
+    pub name: String,
+    pub label: Option<String>,
+    #[serde(deserialize_with = "config::color")]
+    pub color: Option<RGBColor>,
+    pub line_style: Option<LineStyle>,
+    pub facet: Option<String>,
+    pub hidden: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+#[derive(clap::ArgEnum, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
 pub enum RendererInput {
     Violin,
     Line,
+    Histogram,
 }
 
-pub enum Renderer<'a> {
-    Violin(violin::Violin<'a>),
-    Line(line::Line<'a>),
+pub enum Renderer {
+    Violin(violin::Violin),
+    Line(line::Line),
+    Histogram(histogram::Histogram),
 }
 
-impl<'a> Renderer<'a> {
-    pub fn new(input: RendererInput, histograms: HGRMs, filename: &'a str) -> Self {
+impl Renderer {
+    pub fn new(input: RendererInput, histograms: HGRMs, options: RenderOptions) -> Self {
+        let histograms = options.arrange(histograms);
+
         match input {
-            RendererInput::Violin => Renderer::Violin(violin::Violin::new(histograms, filename)),
-            RendererInput::Line => Renderer::Line(line::Line::new(histograms, filename)),
+            RendererInput::Violin => Renderer::Violin(violin::Violin::new(histograms, options)),
+            RendererInput::Line => Renderer::Line(line::Line::new(histograms, options)),
+            RendererInput::Histogram => {
+                Renderer::Histogram(histogram::Histogram::new(histograms, options))
+            }
         }
     }
 
-    pub fn render(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Renders to `filename`, as a bitmap for image extensions such as `.png`
+    /// and as SVG otherwise.
+    pub fn render(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
         match &self {
-            Self::Violin(violin) => violin.render(),
-            Self::Line(line) => line.render(),
+            Self::Violin(violin) => render_file(violin, filename),
+            Self::Line(line) => render_file(line, filename),
+            Self::Histogram(histogram) => render_file(histogram, filename),
+        }
+    }
+
+    pub fn to_svg(&self) -> Result<String, Box<dyn std::error::Error>> {
+        match &self {
+            Self::Violin(violin) => render_svg(violin),
+            Self::Line(line) => render_svg(line),
+            Self::Histogram(histogram) => render_svg(histogram),
+        }
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match &self {
+            Self::Violin(violin) => render_png(violin),
+            Self::Line(line) => render_png(line),
+            Self::Histogram(histogram) => render_png(histogram),
+        }
+    }
+
+    /// Renders in memory, for callers writing the chart somewhere other than
+    /// a file.
+    pub fn to_bytes(&self, format: ImageFormat) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match format {
+            ImageFormat::Svg => Ok(self.to_svg()?.into_bytes()),
+            ImageFormat::Png => self.to_png(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_arrange() {
+        let options = RenderOptions {
+            series: vec![
+                SeriesOptions {
+                    name: "c".to_string(),
+                    label: Some("C".to_string()),
+                    ..Default::default()
+                },
+                SeriesOptions {
+                    name: "b".to_string(),
+                    hidden: true,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let histograms = HGRMs::new(vec![
+            HGRM::new().set_name(Some("a")),
+            HGRM::new().set_name(Some("b")),
+            HGRM::new().set_name(Some("c")),
+            HGRM::new(),
+        ]);
+
+        let arranged = options.arrange(histograms);
+        let names: Vec<String> = arranged
+            .iter()
+            .enumerate()
+            .map(|(idx, hgrm)| options.series_name(idx, hgrm))
+            .collect();
+
+        assert_eq!(names, vec!["C", "a", "#3"]);
+    }
+
+    #[test]
+    fn test_facets() {
+        let options = RenderOptions {
+            facet_by: Some(Regex::new("^([a-z]+)@").unwrap()),
+            series: vec![SeriesOptions {
+                name: "legacy".to_string(),
+                facet: Some("checkout".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let histograms = HGRMs::new(vec![
+            HGRM::new().set_name(Some("checkout@1k")),
+            HGRM::new().set_name(Some("search@1k")),
+            HGRM::new().set_name(Some("checkout@2k")),
+            HGRM::new().set_name(Some("legacy")),
+            HGRM::new(),
+        ]);
+
+        let facets: Vec<(Option<String>, usize)> = options
+            .facets(&histograms)
+            .iter()
+            .map(|facet| (facet.name.clone(), facet.histograms.len()))
+            .collect();
+
+        assert_eq!(
+            facets,
+            vec![
+                (Some("checkout".to_string()), 3),
+                (Some("search".to_string()), 1),
+                (Some("Other".to_string()), 1),
+            ]
+        );
+
+        let unfaceted = RenderOptions::default().facets(&histograms);
+        assert_eq!(unfaceted.len(), 1);
+        assert_eq!(unfaceted[0].name, None);
+    }
+
+    #[test]
+    fn test_grid() {
+        let options = RenderOptions::default();
+        assert_eq!(options.grid(1), (1, 1));
+        assert_eq!(options.grid(3), (2, 2));
+        assert_eq!(options.grid(12), (3, 4));
+
+        let options = RenderOptions {
+            columns: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(options.grid(12), (4, 3));
+        assert_eq!(options.grid(2), (1, 2));
+    }
+
+    #[test]
+    fn test_render_in_memory() {
+        let histograms = HGRMs::new(vec![HGRM::from_buckets(&[(1000.0, 10), (2000.0, 5)])]);
+
+        for input in [
+            RendererInput::Line,
+            RendererInput::Violin,
+            RendererInput::Histogram,
+        ] {
+            let renderer = Renderer::new(input, histograms.clone(), RenderOptions::default());
+
+            assert!(renderer.to_svg().unwrap().starts_with("<svg"));
+            assert!(renderer
+                .to_bytes(ImageFormat::Png)
+                .unwrap()
+                .starts_with(b"\x89PNG"));
         }
     }
 }
@@ -0,0 +1,497 @@
+//! Writes a report of a test campaign: charts, percentile tables and SLO
+//! verdicts for each group of inputs, with an HTML or Markdown index.
+
+use std::fs;
+use std::path::Path;
+
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::hgrm::{HGRMs, HGRM};
+use crate::input::{self, InputOptions};
+use crate::renderer::{ImageFormat, RenderOptions, Renderer, RendererInput};
+use crate::units::{format_percentile, DEFAULT_PERCENTILES};
+
+const DEFAULT_TITLE: &str = "Latency report";
+const VIEWS: [RendererInput; 3] = [
+    RendererInput::Line,
+    RendererInput::Violin,
+    RendererInput::Histogram,
+];
+
+/// Format of the report's index.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum IndexFormat {
+    Html,
+    Markdown,
+}
+
+/// What goes into a report and where it's written.
+#[derive(Debug)]
+pub struct ReportOptions {
+    /// Directory the index and charts are written to.
+    pub output: String,
+    pub index: IndexFormat,
+    pub image: ImageFormat,
+    /// Charts rendered for each group, all of them when empty.
+    pub views: Vec<RendererInput>,
+    /// Groups series by this pattern on their names, instead of by input.
+    pub group_by: Option<Regex>,
+}
+
+/// Histograms reported on together.
+#[derive(Debug)]
+struct Group {
+    name: String,
+    histograms: HGRMs,
+}
+
+/// A table of text cells with a header row.
+#[derive(Debug, PartialEq)]
+struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn markdown(&self) -> String {
+        let row = |cells: &[String]| {
+            format!(
+                "| {} |\n",
+                cells
+                    .iter()
+                    .map(|cell| cell.replace('|', "\\|"))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            )
+        };
+
+        let mut markdown = row(&self.header);
+        markdown.push_str(&row(&vec!["---".to_string(); self.header.len()]));
+        for cells in &self.rows {
+            markdown.push_str(&row(cells));
+        }
+
+        markdown
+    }
+
+    fn html(&self) -> String {
+        let row = |tag: &str, cells: &[String]| {
+            let cells: String = cells
+                .iter()
+                .map(|cell| format!("<{tag}>{}</{tag}>", escape(cell)))
+                .collect();
+
+            format!("<tr>{}</tr>\n", cells)
+        };
+
+        let mut html = String::from("<table>\n<thead>\n");
+        html.push_str(&row("th", &self.header));
+        html.push_str("</thead>\n<tbody>\n");
+        for cells in &self.rows {
+            html.push_str(&row("td", cells));
+        }
+        html.push_str("</tbody>\n</table>\n");
+
+        html
+    }
+}
+
+/// A group's charts, as (view, file name) pairs, and its tables.
+#[derive(Debug)]
+struct Section {
+    name: String,
+    charts: Vec<(String, String)>,
+    table: Table,
+    verdicts: Vec<String>,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Lowercase name safe to use in file names, e.g. `checkout@2k` becomes
+/// `checkout-2k`.
+fn slug(name: &str) -> String {
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if slug.is_empty() {
+        "group".to_string()
+    } else {
+        slug
+    }
+}
+
+fn view_name(view: &RendererInput) -> &'static str {
+    match view {
+        RendererInput::Line => "line",
+        RendererInput::Violin => "violin",
+        RendererInput::Histogram => "histogram",
+    }
+}
+
+/// Groups series by `group_by` on their names when given, otherwise one
+/// group per input named after its file.
+fn groups(
+    inputs: &[String],
+    input_options: &InputOptions,
+    options: &RenderOptions,
+    group_by: &Option<Regex>,
+) -> Result<Vec<Group>, Box<dyn std::error::Error>> {
+    let mut loaded = vec![];
+
+    for (idx, input) in inputs.iter().enumerate() {
+        let name = if Path::new(input).exists() {
+            Path::new(input)
+                .file_stem()
+                .map_or(input.clone(), |stem| stem.to_string_lossy().into_owned())
+        } else {
+            format!("data {}", idx + 1)
+        };
+
+        loaded.push((name, input::load(input, input_options)?));
+    }
+
+    let Some(group_by) = group_by else {
+        return Ok(loaded
+            .into_iter()
+            .map(|(name, histograms)| Group { name, histograms })
+            .collect());
+    };
+
+    let options = RenderOptions {
+        facet_by: Some(group_by.clone()),
+        ..options.clone()
+    };
+    let histograms = HGRMs::new(
+        loaded
+            .into_iter()
+            .flat_map(|(_, histograms)| histograms)
+            .collect(),
+    );
+
+    Ok(options
+        .facets(&histograms)
+        .into_iter()
+        .map(|facet| Group {
+            name: facet.name.unwrap_or_else(|| "All".to_string()),
+            histograms: facet.histograms,
+        })
+        .collect())
+}
+
+/// Percentiles, in milliseconds, of each series in the group with a column
+/// per SLO giving its verdict.
+fn table(histograms: &HGRMs, options: &RenderOptions) -> Table {
+    let percentiles: Vec<f64> = if options.annotate.is_empty() {
+        DEFAULT_PERCENTILES.to_vec()
+    } else {
+        options.annotate.clone()
+    };
+
+    let unit = options.latency_unit;
+    let header = ["Series".to_string(), "Total".to_string()]
+        .into_iter()
+        .chain(percentiles.iter().map(|p| format_percentile(*p)))
+        .chain(std::iter::once("Max".to_string()))
+        .chain(options.slos.iter().map(|slo| slo.to_string()))
+        .collect();
+
+    let rows = histograms
+        .iter()
+        .enumerate()
+        .map(|(idx, histogram): (usize, &HGRM)| {
+            [
+                options.series_name(idx, histogram),
+                histogram.total_count.to_string(),
+            ]
+            .into_iter()
+            .chain(
+                percentiles
+                    .iter()
+                    .map(|p| unit.format(histogram.value_at_percentile(*p))),
+            )
+            .chain(std::iter::once(unit.format(histogram.max)))
+            .chain(options.slos.iter().map(|slo| {
+                let value = unit.format(histogram.value_at_percentile(slo.percentile));
+
+                if slo.is_violated_by(histogram) {
+                    format!("✗ {}", value)
+                } else {
+                    format!("✓ {}", value)
+                }
+            }))
+            .collect()
+        })
+        .collect();
+
+    Table { header, rows }
+}
+
+fn markdown(title: &str, sections: &[Section]) -> String {
+    let mut markdown = format!("# {}\n", title);
+
+    for section in sections {
+        markdown.push_str(&format!("\n## {}\n\n", section.name));
+
+        for (view, file) in &section.charts {
+            markdown.push_str(&format!("![{} {}]({})\n\n", section.name, view, file));
+        }
+
+        markdown.push_str(&section.table.markdown());
+
+        if !section.verdicts.is_empty() {
+            markdown.push('\n');
+            for verdict in &section.verdicts {
+                markdown.push_str(&format!("- {}\n", verdict));
+            }
+        }
+    }
+
+    markdown
+}
+
+fn html(title: &str, sections: &[Section]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em auto; max-width: 1000px; }}
+img {{ max-width: 100%; }}
+table {{ border-collapse: collapse; margin: 1em 0; }}
+th, td {{ border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: right; }}
+th:first-child, td:first-child {{ text-align: left; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+",
+        title = escape(title)
+    );
+
+    for section in sections {
+        html.push_str(&format!("<h2>{}</h2>\n", escape(&section.name)));
+
+        for (view, file) in &section.charts {
+            html.push_str(&format!(
+                "<p><a href=\"{file}\"><img src=\"{file}\" alt=\"{}\"></a></p>\n",
+                escape(&format!("{} {}", section.name, view)),
+                file = escape(file)
+            ));
+        }
+
+        html.push_str(&section.table.html());
+
+        if !section.verdicts.is_empty() {
+            html.push_str("<ul>\n");
+            for verdict in &section.verdicts {
+                html.push_str(&format!("<li>{}</li>\n", escape(verdict)));
+            }
+            html.push_str("</ul>\n");
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
+
+/// Writes charts for each group of `inputs`, and an index linking them, to
+/// the report's output directory. Returns the path to the index.
+pub fn report(
+    inputs: &[String],
+    input_options: &InputOptions,
+    options: &RenderOptions,
+    report: &ReportOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let groups = groups(inputs, input_options, options, &report.group_by)?;
+    let views = if report.views.is_empty() {
+        VIEWS.to_vec()
+    } else {
+        report.views.clone()
+    };
+    let extension = match report.image {
+        ImageFormat::Svg => "svg",
+        ImageFormat::Png => "png",
+    };
+
+    fs::create_dir_all(&report.output)?;
+
+    let mut sections = vec![];
+    let mut used = vec![];
+
+    for group in &groups {
+        // Names which only differ in punctuation would share files
+        let mut slug = slug(&group.name);
+        while used.contains(&slug) {
+            slug.push('_');
+        }
+        used.push(slug.clone());
+
+        let charts: Vec<(String, String)> = views
+            .iter()
+            .map(|view| {
+                (
+                    view_name(view).to_string(),
+                    format!("{}-{}.{}", slug, view_name(view), extension),
+                )
+            })
+            .collect();
+
+        let chart_options = RenderOptions {
+            title: Some(group.name.clone()),
+            ..options.clone()
+        };
+
+        views
+            .par_iter()
+            .zip(&charts)
+            .map(|(view, (_, file))| {
+                let renderer = Renderer::new(
+                    view.clone(),
+                    group.histograms.clone(),
+                    chart_options.clone(),
+                );
+                let path = Path::new(&report.output).join(file);
+
+                renderer
+                    .render(&path.to_string_lossy())
+                    .map_err(|e| format!("Unable to render {}: {}", file, e))
+            })
+            .collect::<Result<Vec<()>, String>>()?;
+
+        let histograms = options.arrange(group.histograms.clone());
+
+        sections.push(Section {
+            name: group.name.clone(),
+            charts,
+            table: table(&histograms, options),
+            verdicts: options
+                .slos
+                .iter()
+                .map(|slo| {
+                    slo.legend(&histograms, |idx, histogram| {
+                        options.series_name(idx, histogram)
+                    })
+                })
+                .collect(),
+        });
+    }
+
+    let title = options.title.as_deref().unwrap_or(DEFAULT_TITLE);
+    let (index, contents) = match report.index {
+        IndexFormat::Html => ("index.html", html(title, &sections)),
+        IndexFormat::Markdown => ("index.md", markdown(title, &sections)),
+    };
+    let index = Path::new(&report.output).join(index);
+
+    fs::write(&index, contents)?;
+
+    Ok(index.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slo::Slo;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("checkout@2k"), "checkout-2k");
+        assert_eq!(slug("  Search / v2 "), "search-v2");
+        assert_eq!(slug("@@"), "group");
+    }
+
+    #[test]
+    fn test_table() {
+        let histograms = HGRMs::new(vec![
+            HGRM::from_buckets(&[(1000.0, 50), (2000.0, 50)]).set_name(Some("a")),
+            HGRM::from_buckets(&[(1000.0, 50), (80000.0, 50)]).set_name(Some("b|c")),
+        ]);
+        let options = RenderOptions {
+            annotate: vec![0.5],
+            slos: vec![Slo::new(0.99, 50000.0)],
+            ..Default::default()
+        };
+
+        let table = table(&histograms, &options);
+
+        assert_eq!(
+            table,
+            Table {
+                header: vec![
+                    "Series".to_string(),
+                    "Total".to_string(),
+                    "p50".to_string(),
+                    "Max".to_string(),
+                    "p99 < 50ms".to_string(),
+                ],
+                rows: vec![
+                    vec![
+                        "a".to_string(),
+                        "100".to_string(),
+                        "1ms".to_string(),
+                        "2ms".to_string(),
+                        "✓ 2ms".to_string(),
+                    ],
+                    vec![
+                        "b|c".to_string(),
+                        "100".to_string(),
+                        "1ms".to_string(),
+                        "80ms".to_string(),
+                        "✗ 80ms".to_string(),
+                    ],
+                ],
+            }
+        );
+        assert!(table.markdown().contains("| b\\|c | 100 |"));
+        assert!(table.html().contains("<td>✗ 80ms</td>"));
+    }
+
+    #[test]
+    fn test_report() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        let input = dir.join("runs.txt");
+        fs::write(&input, "checkout\n1000\n1200\n1500\n3000\n").unwrap();
+
+        let report_options = ReportOptions {
+            output: dir.join("report").to_string_lossy().into_owned(),
+            index: IndexFormat::Markdown,
+            image: ImageFormat::Svg,
+            views: vec![],
+            group_by: None,
+        };
+        let index = report(
+            &[input.to_string_lossy().into_owned()],
+            &InputOptions::default(),
+            &RenderOptions::default(),
+            &report_options,
+        )
+        .unwrap();
+
+        let contents = fs::read_to_string(&index).unwrap();
+        assert!(contents.starts_with("# Latency report\n\n## runs\n"));
+        for view in ["line", "violin", "histogram"] {
+            let file = format!("runs-{}.svg", view);
+
+            assert!(contents.contains(&format!("(runs-{}.svg)", view)));
+            assert!(dir.join("report").join(file).is_file());
+        }
+    }
+}
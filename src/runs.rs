@@ -0,0 +1,219 @@
+//! Aggregates repeated runs of a scenario into their median and spread.
+
+use itertools::Itertools;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::hgrm::{HGRMs, OnePercentile, Percentile, HGRM};
+
+/// Spread of aggregated runs shaded around their median.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Band {
+    /// From the fastest to the slowest run at each percentile
+    #[default]
+    MinMax,
+    /// From the first to the third quartile of runs at each percentile
+    Iqr,
+}
+
+/// Runs sharing a key, drawn as their median with a band.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aggregate {
+    /// Median of the runs at each percentile, named after their key.
+    pub median: HGRM,
+    /// `(percentile, low, high)` edges of the band, in microseconds.
+    pub band: Vec<(f64, f64, f64)>,
+    pub runs: usize,
+}
+
+/// A series once runs are grouped.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Group {
+    /// A histogram sharing its key with no other, or without a key.
+    Single(HGRM),
+    /// Runs sharing a key.
+    Aggregated(Aggregate),
+}
+
+/// Key of the runs a histogram belongs to, taken from its name by `pattern`
+/// using the first capture group when the pattern has one.
+fn key(pattern: &Regex, hgrm: &HGRM) -> Option<String> {
+    let captures = pattern.captures(hgrm.name.as_ref()?)?;
+    let key = captures.get(1).or_else(|| captures.get(0))?;
+
+    Some(key.as_str().to_string())
+}
+
+/// Value of `hgrm` at `percentile`, interpolated linearly between the
+/// percentile rows around it.
+fn interpolate(hgrm: &HGRM, percentile: f64) -> f64 {
+    let rows = &hgrm.percentiles;
+    let idx = rows.partition_point(|row| row.percentile < percentile);
+
+    match (idx.checked_sub(1).map(|i| &rows[i]), rows.get(idx)) {
+        (_, Some(next)) if next.percentile == percentile => next.value,
+        (Some(prev), Some(next)) => {
+            prev.value
+                + (next.value - prev.value) * (percentile - prev.percentile)
+                    / (next.percentile - prev.percentile)
+        }
+        (None, Some(next)) => next.value,
+        (Some(prev), None) => prev.value,
+        (None, None) => 0.0,
+    }
+}
+
+/// Quantile `q` of sorted `values`, interpolated between the closest two.
+fn quantile(values: &[f64], q: f64) -> f64 {
+    let position = q * (values.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+
+    values[lower] + (values[upper] - values[lower]) * (position - lower as f64)
+}
+
+fn median(values: impl Iterator<Item = f64>) -> f64 {
+    let values: Vec<f64> = values.sorted_by(|a, b| a.partial_cmp(b).unwrap()).collect();
+
+    quantile(&values, 0.5)
+}
+
+/// Aggregates `runs` onto the percentiles recorded by any of them.
+fn aggregate(name: &str, runs: &[&HGRM], band: Band) -> Aggregate {
+    let grid: Vec<f64> = runs
+        .iter()
+        .flat_map(|run| run.percentiles.iter().map(|row| row.percentile))
+        .filter(|percentile| *percentile < 1.0)
+        .sorted_by(|a, b| a.partial_cmp(b).unwrap())
+        .dedup()
+        .collect();
+
+    let total_count = median(runs.iter().map(|run| run.total_count as f64)).round() as u64;
+    let (low, high) = match band {
+        Band::MinMax => (0.0, 1.0),
+        Band::Iqr => (0.25, 0.75),
+    };
+
+    let mut percentiles = vec![];
+    let mut edges = vec![];
+
+    for percentile in grid {
+        let values: Vec<f64> = runs
+            .iter()
+            .map(|run| interpolate(run, percentile))
+            .sorted_by(|a, b| a.partial_cmp(b).unwrap())
+            .collect();
+
+        percentiles.push(Percentile::new(
+            quantile(&values, 0.5),
+            percentile,
+            (percentile * total_count as f64).round() as u64,
+            OnePercentile::Value(1.0 / (1.0 - percentile)),
+        ));
+        edges.push((percentile, quantile(&values, low), quantile(&values, high)));
+    }
+
+    let max = median(runs.iter().map(|run| run.max));
+    percentiles.push(Percentile::new(max, 1.0, total_count, OnePercentile::Inf));
+
+    Aggregate {
+        median: HGRM::new()
+            .set_name(Some(name))
+            .set_mean(median(runs.iter().map(|run| run.mean)))
+            .set_std_deviation(median(runs.iter().map(|run| run.std_deviation)))
+            .set_max(max)
+            .set_total_count(total_count)
+            .set_percentiles(percentiles),
+        band: edges,
+        runs: runs.len(),
+    }
+}
+
+/// Groups histograms whose names share a key matched by `pattern`, in order
+/// of first appearance. Groups of more than one run are aggregated, others
+/// are returned unchanged.
+pub fn group(histograms: &HGRMs, pattern: &Regex, band: Band) -> Vec<Group> {
+    let keys: Vec<Option<String>> = histograms.iter().map(|h| key(pattern, h)).collect();
+    let mut groups = vec![];
+
+    for (idx, histogram) in histograms.iter().enumerate() {
+        let Some(key) = &keys[idx] else {
+            groups.push(Group::Single(histogram.clone()));
+            continue;
+        };
+
+        if keys[..idx].contains(&keys[idx]) {
+            continue;
+        }
+
+        let runs: Vec<&HGRM> = histograms
+            .iter()
+            .zip(&keys)
+            .filter(|(_, k)| k.as_ref() == Some(key))
+            .map(|(h, _)| h)
+            .collect();
+
+        groups.push(match runs.len() {
+            1 => Group::Single(histogram.clone()),
+            _ => Group::Aggregated(aggregate(key, &runs, band)),
+        });
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn run(name: &str, values: [f64; 3]) -> HGRM {
+        HGRM::new()
+            .set_name(Some(name))
+            .set_total_count(100)
+            .set_max(values[2])
+            .add_percentile(values[0], 0.0, 1, OnePercentile::Value(1.0))
+            .add_percentile(values[1], 0.5, 50, OnePercentile::Value(2.0))
+            .add_percentile(values[2], 1.0, 100, OnePercentile::Inf)
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let hgrm = run("a", [100.0, 200.0, 400.0]);
+
+        assert_eq!(interpolate(&hgrm, 0.25), 150.0);
+        assert_eq!(interpolate(&hgrm, 0.5), 200.0);
+        assert_eq!(interpolate(&hgrm, 0.75), 300.0);
+    }
+
+    #[test]
+    fn test_group() {
+        let histograms = HGRMs::new(vec![
+            run("checkout#run1", [100.0, 200.0, 400.0]),
+            run("search", [100.0, 100.0, 100.0]),
+            run("checkout#run2", [110.0, 240.0, 500.0]),
+            run("checkout#run3", [90.0, 180.0, 800.0]),
+        ]);
+        let pattern = Regex::new("^(.+)#run").unwrap();
+
+        let groups = group(&histograms, &pattern, Band::MinMax);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1], Group::Single(histograms[1].clone()));
+
+        let Group::Aggregated(checkout) = &groups[0] else {
+            panic!("checkout runs should be aggregated");
+        };
+        assert_eq!(checkout.runs, 3);
+        assert_eq!(checkout.median.name.as_deref(), Some("checkout"));
+        assert_eq!(checkout.median.value_at_percentile(0.5), 200.0);
+        assert_eq!(checkout.median.max, 500.0);
+        assert_eq!(checkout.band, vec![(0.0, 90.0, 110.0), (0.5, 180.0, 240.0)]);
+
+        let Group::Aggregated(iqr) = &group(&histograms, &pattern, Band::Iqr)[0] else {
+            panic!("checkout runs should be aggregated");
+        };
+        assert_eq!(iqr.band, vec![(0.0, 95.0, 105.0), (0.5, 190.0, 220.0)]);
+    }
+}
@@ -0,0 +1,359 @@
+//! A small HTTP server rendering histograms POSTed to it, for load testing
+//! harnesses which would rather not shell out.
+
+use std::io::Read;
+
+use clap::Parser;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::hgrm::HGRMs;
+use crate::input;
+use crate::renderer::{ImageFormat, RenderOptions, Renderer};
+use crate::{InputArgs, RenderArgs};
+
+/// Percentiles reported in JSON stats.
+const STATS_PERCENTILES: [f64; 6] = [0.5, 0.75, 0.9, 0.99, 0.999, 0.9999];
+/// Largest request body read, in bytes.
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// What a request is answered with.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ResponseFormat {
+    Svg,
+    Png,
+    /// Percentiles and SLO verdicts for each histogram, in microseconds
+    Json,
+}
+
+impl ResponseFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Svg => "image/svg+xml",
+            ResponseFormat::Png => "image/png",
+            ResponseFormat::Json => "application/json",
+        }
+    }
+}
+
+/// Options taken from a request's query string, the same as the command
+/// line flags without their leading dashes, e.g. `?renderer=violin&log-y`.
+/// Flags naming files on the server, such as `config`, are refused.
+#[derive(Parser, Debug)]
+#[clap(name = "query", color = clap::ColorChoice::Never)]
+struct QueryArgs {
+    /// Format of the response
+    #[clap(arg_enum, long, default_value = "svg")]
+    format: ResponseFormat,
+
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    render: RenderArgs,
+}
+
+impl QueryArgs {
+    /// Parses a query string as flags, keys without a value or set to `true`
+    /// becoming switches.
+    fn parse(query: &str) -> Result<Self, String> {
+        let flags = form_urlencoded::parse(query.as_bytes()).flat_map(|(key, value)| {
+            let flag = format!("--{}", key);
+
+            match value.as_ref() {
+                "" | "true" => vec![flag],
+                _ => vec![flag, value.into_owned()],
+            }
+        });
+
+        let args = Self::try_parse_from(std::iter::once("query".to_string()).chain(flags))
+            .map_err(|e| e.to_string())?;
+
+        if args.render.config.is_some() {
+            return Err("A config file can't be given in a query string".to_string());
+        }
+
+        Ok(args)
+    }
+}
+
+/// An error answered with `status` and a plain text message.
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Percentiles and SLO verdicts for each histogram, as given to `render`.
+fn stats(histograms: &HGRMs, options: &RenderOptions) -> Value {
+    let series: Vec<Value> = histograms
+        .iter()
+        .enumerate()
+        .map(|(idx, histogram)| {
+            let percentiles: serde_json::Map<String, Value> = STATS_PERCENTILES
+                .iter()
+                .map(|p| {
+                    (
+                        format!("p{}", p * 100.0),
+                        json!(histogram.value_at_percentile(*p)),
+                    )
+                })
+                .collect();
+            let slos: Vec<Value> = options
+                .slos
+                .iter()
+                .map(|slo| {
+                    json!({
+                        "percentile": slo.percentile,
+                        "threshold": slo.threshold,
+                        "value": histogram.value_at_percentile(slo.percentile),
+                        "violated": slo.is_violated_by(histogram),
+                    })
+                })
+                .collect();
+
+            json!({
+                "name": options.series_name(idx, histogram),
+                "total_count": histogram.total_count,
+                "mean": histogram.mean,
+                "std_deviation": histogram.std_deviation,
+                "max": histogram.max,
+                "percentiles": percentiles,
+                "slos": slos,
+            })
+        })
+        .collect();
+
+    json!({ "histograms": series })
+}
+
+/// Renders the histograms POSTed in `body` as asked for by `query`.
+fn respond(query: &str, body: &[u8]) -> Result<(ResponseFormat, Vec<u8>), HttpError> {
+    let args = QueryArgs::parse(query).map_err(|e| HttpError::new(400, e))?;
+    let options = args.input.options();
+
+    let (data, _) = input::unwrap(body.to_vec())
+        .map_err(|e| HttpError::new(400, format!("Unable to decode body: {}", e)))?;
+    let histograms = input::parse(&data, &options)
+        .map_err(|e| HttpError::new(400, format!("Unable to parse body: {}", e)))?;
+    let (renderer, options) = args.render.resolve().map_err(|e| HttpError::new(400, e))?;
+
+    if args.format == ResponseFormat::Json {
+        let histograms = options.arrange(histograms);
+
+        return Ok((
+            args.format,
+            stats(&histograms, &options).to_string().into_bytes(),
+        ));
+    }
+
+    let format = match args.format {
+        ResponseFormat::Png => ImageFormat::Png,
+        _ => ImageFormat::Svg,
+    };
+
+    Renderer::new(renderer, histograms, options)
+        .to_bytes(format)
+        .map(|chart| (args.format, chart))
+        .map_err(|e| HttpError::new(500, format!("Unable to render chart: {}", e)))
+}
+
+fn too_large() -> HttpError {
+    HttpError::new(
+        413,
+        format!("Request body is larger than {} bytes", MAX_BODY_SIZE),
+    )
+}
+
+fn handle(mut request: Request) -> std::io::Result<()> {
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (request.url().to_string(), String::new()),
+    };
+
+    let result = if path != "/" {
+        Err(HttpError::new(404, "Not found, POST histograms to /"))
+    } else if *request.method() != Method::Post {
+        Err(HttpError::new(405, "POST histograms to /"))
+    } else if request
+        .body_length()
+        .is_some_and(|length| length > MAX_BODY_SIZE)
+    {
+        Err(too_large())
+    } else {
+        let mut body = vec![];
+
+        // Without a Content-Length, the body is only known to be too large
+        // once more than the limit has been read
+        match Read::take(request.as_reader(), MAX_BODY_SIZE as u64 + 1).read_to_end(&mut body) {
+            Ok(_) if body.len() > MAX_BODY_SIZE => Err(too_large()),
+            Ok(_) => respond(&query, &body),
+            Err(e) => Err(HttpError::new(400, e)),
+        }
+    };
+
+    let (status, content_type, body) = match result {
+        Ok((format, body)) => (200, format.content_type(), body),
+        Err(e) => (
+            e.status,
+            "text/plain; charset=utf-8",
+            e.message.into_bytes(),
+        ),
+    };
+
+    println!("{} {} {}", request.method(), request.url(), status);
+
+    let header = Header::from_bytes("Content-Type", content_type)
+        .expect("Content-Type header should be valid");
+
+    request.respond(
+        Response::from_data(body)
+            .with_status_code(status)
+            .with_header(header),
+    )
+}
+
+/// Answers requests to `server` until it's closed.
+fn run(server: &Server) {
+    for request in server.incoming_requests() {
+        if let Err(e) = handle(request) {
+            eprintln!("Unable to respond: {}", e);
+        }
+    }
+}
+
+/// Serves rendered histograms on `address`, e.g. `127.0.0.1:8080`.
+pub fn serve(address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let server =
+        Server::http(address).map_err(|e| format!("Unable to listen on {}: {}", address, e))?;
+
+    println!("Listening on http://{}", server.server_addr());
+
+    run(&server);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::{Shutdown, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    const HGRM: &str = include_str!("../test/data/12Krps.hgrm");
+
+    /// Sends a request to a server started on a free local port, returning
+    /// the status and body of its response.
+    fn request(method: &str, url: &str, body: &[u8]) -> (u16, Vec<u8>) {
+        let head = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+            method,
+            url,
+            body.len()
+        );
+
+        send(&[head.as_bytes(), body].concat())
+    }
+
+    /// Sends a raw HTTP request to a server started on a free local port.
+    fn send(raw: &[u8]) -> (u16, Vec<u8>) {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let address = server.server_addr().to_ip().unwrap();
+        let handler = {
+            let server = Arc::clone(&server);
+            thread::spawn(move || handle(server.recv().unwrap()).unwrap())
+        };
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(raw).unwrap();
+        // Ends a body shorter than its Content-Length
+        stream.shutdown(Shutdown::Write).unwrap();
+
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        handler.join().unwrap();
+
+        let split = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap();
+        let head = String::from_utf8_lossy(&response[..split]);
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+
+        (status, response[split + 4..].to_vec())
+    }
+
+    #[test]
+    fn test_query_args() {
+        let args =
+            QueryArgs::parse("renderer=violin&log-y&title=Checkout+p99&format=json").unwrap();
+
+        assert_eq!(args.format, ResponseFormat::Json);
+        assert!(args.render.log_y);
+        assert_eq!(args.render.title.as_deref(), Some("Checkout p99"));
+        assert!(QueryArgs::parse("colour=red").is_err());
+        assert!(QueryArgs::parse("config=/etc/passwd").is_err());
+        assert!(QueryArgs::parse("title=a&config").is_err());
+    }
+
+    #[test]
+    fn test_serve_svg() {
+        let (status, body) = request("POST", "/?renderer=line&title=Test", HGRM.as_bytes());
+
+        assert_eq!(status, 200);
+        assert!(String::from_utf8(body).unwrap().contains("<svg"));
+    }
+
+    #[test]
+    fn test_serve_png() {
+        let payload = base64::encode(HGRM);
+        let (status, body) = request("POST", "/?format=png", payload.as_bytes());
+
+        assert_eq!(status, 200);
+        assert!(body.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn test_serve_json() {
+        let (status, body) = request("POST", "/?format=json&slo=0.5:1ms", HGRM.as_bytes());
+        let stats: Value = serde_json::from_slice(&body).unwrap();
+        let histogram = &stats["histograms"][0];
+
+        assert_eq!(status, 200);
+        assert_eq!(histogram["total_count"], 3477000);
+        // wrk2 writes milliseconds, reported in microseconds
+        assert_eq!(histogram["percentiles"]["p50"], 4919.0);
+        assert_eq!(histogram["slos"][0]["violated"], true);
+    }
+
+    #[test]
+    fn test_serve_errors() {
+        assert_eq!(request("GET", "/", b"").0, 405);
+        assert_eq!(request("POST", "/charts", HGRM.as_bytes()).0, 404);
+        assert_eq!(request("POST", "/?renderer=pie", HGRM.as_bytes()).0, 400);
+        assert_eq!(request("POST", "/", b"hello world").0, 400);
+
+        let (status, body) = request("POST", "/?config=/etc/passwd", HGRM.as_bytes());
+        assert_eq!(status, 400);
+        assert!(!String::from_utf8(body).unwrap().contains("root:"));
+
+        let claimed = format!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_SIZE + 1
+        );
+        assert_eq!(send(claimed.as_bytes()).0, 413);
+    }
+}
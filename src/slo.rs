@@ -0,0 +1,131 @@
+use std::{fmt, str::FromStr};
+
+use crate::hgrm::{HGRMs, HGRM};
+use crate::units::{
+    format_millis, format_percentile, parse_duration, parse_percentile, LatencyUnit,
+};
+
+/// A service level objective: `percentile` of requests complete within
+/// `threshold` microseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slo {
+    pub percentile: f64,
+    pub threshold: f64,
+}
+
+impl Slo {
+    pub fn new(percentile: f64, threshold: f64) -> Self {
+        Self {
+            percentile,
+            threshold,
+        }
+    }
+
+    pub fn threshold_in(&self, unit: LatencyUnit) -> f64 {
+        unit.convert(self.threshold)
+    }
+
+    pub fn is_violated_by(&self, hgrm: &HGRM) -> bool {
+        hgrm.value_at_percentile(self.percentile) > self.threshold
+    }
+
+    /// Names of the histograms violating this objective, as given by `name`.
+    pub fn violators<F>(&self, histograms: &HGRMs, name: F) -> Vec<String>
+    where
+        F: Fn(usize, &HGRM) -> String,
+    {
+        histograms
+            .iter()
+            .enumerate()
+            .filter(|(_, hgrm)| self.is_violated_by(hgrm))
+            .map(|(idx, hgrm)| name(idx, hgrm))
+            .collect()
+    }
+
+    /// Legend entry summarising which histograms violate the objective.
+    pub fn legend<F>(&self, histograms: &HGRMs, name: F) -> String
+    where
+        F: Fn(usize, &HGRM) -> String,
+    {
+        let violators = self.violators(histograms, name);
+
+        if violators.is_empty() {
+            format!("{}: met", self)
+        } else {
+            format!("{}: violated by {}", self, violators.join(", "))
+        }
+    }
+}
+
+impl fmt::Display for Slo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} < {}",
+            format_percentile(self.percentile),
+            format_millis(self.threshold)
+        )
+    }
+}
+
+impl FromStr for Slo {
+    type Err = String;
+
+    /// Parses `<percentile>:<duration>`, e.g. `0.99:50ms`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (percentile, threshold) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected <percentile>:<duration>, got `{}`", s))?;
+
+        Ok(Self::new(
+            parse_percentile(percentile)?,
+            parse_duration(threshold)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{parse, InputOptions};
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_slo() {
+        assert_eq!("0.99:50ms".parse(), Ok(Slo::new(0.99, 50_000.0)));
+        assert_eq!(
+            "0.999:200ms".parse::<Slo>().unwrap().to_string(),
+            "p99.9 < 200ms"
+        );
+        assert!("99:50ms".parse::<Slo>().is_err());
+        assert!("1:50ms".parse::<Slo>().is_err());
+        assert!("1.0:50ms".parse::<Slo>().is_err());
+        assert_eq!("0:50ms".parse(), Ok(Slo::new(0.0, 50_000.0)));
+        assert!("0.99".parse::<Slo>().is_err());
+    }
+
+    #[test]
+    fn test_violators() {
+        let data = [
+            include_str!("../test/data/12Krps.hgrm"),
+            include_str!("../test/data/16Krps.hgrm"),
+        ];
+
+        // p50 is 4.9ms and 8.1ms, p99 9.3ms and 22.7ms
+        let parsed = parse(&data.join(""), &InputOptions::default()).unwrap();
+
+        let name = |idx: usize, _: &HGRM| format!("#{}", idx + 1);
+
+        let slo: Slo = "0.99:10ms".parse().unwrap();
+        assert_eq!(slo.violators(&parsed, name), vec!["#2"]);
+        assert_eq!(slo.legend(&parsed, name), "p99 < 10ms: violated by #2");
+
+        let slo: Slo = "0.5:1ms".parse().unwrap();
+        assert_eq!(slo.legend(&parsed, name), "p50 < 1ms: violated by #1, #2");
+
+        let slo: Slo = "0.5:50ms".parse().unwrap();
+        assert_eq!(slo.legend(&parsed, name), "p50 < 50ms: met");
+    }
+}
@@ -0,0 +1,186 @@
+use plotters::prelude::*;
+use plotters::style::Palette;
+use serde::Deserialize;
+
+/// Series colours, leaving out vermillion for `Theme::highlight`.
+const OKABE_ITO: [RGBColor; 7] = [
+    RGBColor(230, 159, 0),
+    RGBColor(86, 180, 233),
+    RGBColor(0, 158, 115),
+    RGBColor(240, 228, 66),
+    RGBColor(0, 114, 178),
+    RGBColor(204, 121, 167),
+    RGBColor(0, 0, 0),
+];
+
+/// Series colours, leaving out the yellow end of the scale which hardly
+/// shows on the light background.
+const VIRIDIS: [RGBColor; 7] = [
+    RGBColor(68, 1, 84),
+    RGBColor(33, 145, 140),
+    RGBColor(59, 82, 139),
+    RGBColor(94, 201, 98),
+    RGBColor(72, 40, 120),
+    RGBColor(44, 114, 142),
+    RGBColor(173, 220, 48),
+];
+
+/// Series colours, leaving out red for `Theme::highlight`.
+const DARK: [RGBColor; 7] = [
+    RGBColor(102, 194, 255),
+    RGBColor(255, 170, 80),
+    RGBColor(120, 220, 120),
+    RGBColor(190, 150, 255),
+    RGBColor(230, 200, 90),
+    RGBColor(255, 140, 210),
+    RGBColor(120, 230, 230),
+];
+
+/// Series colours, leaving out red for `Theme::highlight`.
+const HIGH_CONTRAST: [RGBColor; 5] = [
+    RGBColor(0, 0, 0),
+    RGBColor(0, 0, 220),
+    RGBColor(0, 130, 0),
+    RGBColor(160, 0, 160),
+    RGBColor(200, 100, 0),
+];
+
+/// Parses a `#rrggbb` hex colour.
+pub fn parse_color(s: &str) -> Result<RGBColor, String> {
+    let hex = s.trim().trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| format!("Invalid colour `{}`, expected #rrggbb", s))
+    };
+
+    if hex.len() != 6 {
+        return Err(format!("Invalid colour `{}`, expected #rrggbb", s));
+    }
+
+    Ok(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Colours and line styles applied consistently by every renderer.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+    HighContrast,
+    OkabeIto,
+    Viridis,
+}
+
+impl Theme {
+    pub fn background(&self) -> RGBColor {
+        match self {
+            Theme::Dark => RGBColor(30, 30, 30),
+            _ => WHITE,
+        }
+    }
+
+    pub fn text(&self) -> RGBColor {
+        match self {
+            Theme::Dark => RGBColor(220, 220, 220),
+            _ => BLACK,
+        }
+    }
+
+    pub fn axis(&self) -> RGBColor {
+        match self {
+            Theme::Dark => RGBColor(160, 160, 160),
+            _ => BLACK,
+        }
+    }
+
+    pub fn bold_grid(&self) -> RGBAColor {
+        match self {
+            Theme::Dark => RGBColor(255, 255, 255).mix(0.2),
+            Theme::HighContrast => BLACK.mix(0.4),
+            _ => BLACK.mix(0.2),
+        }
+    }
+
+    pub fn light_grid(&self) -> RGBAColor {
+        match self {
+            Theme::Dark => RGBColor(255, 255, 255).mix(0.07),
+            Theme::HighContrast => BLACK.mix(0.15),
+            _ => BLACK.mix(0.05),
+        }
+    }
+
+    pub fn legend_background(&self) -> RGBAColor {
+        match self {
+            Theme::Dark => RGBColor(45, 45, 45).mix(0.9),
+            Theme::HighContrast => WHITE.mix(1.0),
+            _ => WHITE.mix(0.8),
+        }
+    }
+
+    pub fn legend_border(&self) -> RGBColor {
+        match self {
+            Theme::Dark => RGBColor(150, 150, 150),
+            _ => BLACK,
+        }
+    }
+
+    /// Colour used to flag threshold violations.
+    pub fn highlight(&self) -> RGBColor {
+        match self {
+            Theme::Dark => RGBColor(255, 80, 80),
+            Theme::OkabeIto => RGBColor(213, 94, 0),
+            Theme::Viridis => RGBColor(220, 50, 32),
+            _ => RED,
+        }
+    }
+
+    pub fn series_color(&self, idx: usize) -> RGBColor {
+        let pick = |colors: &[RGBColor]| colors[idx % colors.len()];
+
+        match self {
+            Theme::Light => {
+                let (r, g, b) = Palette99::COLORS[idx % Palette99::COLORS.len()];
+                RGBColor(r, g, b)
+            }
+            Theme::Dark => pick(&DARK),
+            Theme::HighContrast => pick(&HIGH_CONTRAST),
+            Theme::OkabeIto => pick(&OKABE_ITO),
+            Theme::Viridis => pick(&VIRIDIS),
+        }
+    }
+
+    pub fn stroke_width(&self) -> u32 {
+        match self {
+            Theme::HighContrast => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ArgEnum;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#1f78b4"), Ok(RGBColor(31, 120, 180)));
+        assert_eq!(parse_color("FF0000"), Ok(RGBColor(255, 0, 0)));
+        assert!(parse_color("#fff").is_err());
+        assert!(parse_color("#gg0000").is_err());
+    }
+
+    #[test]
+    fn test_highlight_isnt_a_series_color() {
+        for theme in Theme::value_variants() {
+            for idx in 0..16 {
+                assert_ne!(theme.highlight(), theme.series_color(idx), "{:?}", theme);
+            }
+        }
+    }
+}
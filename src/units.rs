@@ -0,0 +1,179 @@
+use serde::Deserialize;
+
+/// Histogram values are recorded in microseconds and charted in milliseconds
+/// unless another `LatencyUnit` is chosen.
+pub const MICROS_PER_MILLI: f64 = 1000.0;
+
+/// Percentiles tabulated and compared when none are chosen.
+pub const DEFAULT_PERCENTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+
+/// Unit latencies are charted and tabulated in.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LatencyUnit {
+    Us,
+    #[default]
+    Ms,
+    S,
+}
+
+impl LatencyUnit {
+    /// Converts a value in microseconds to this unit.
+    pub fn convert(self, value: f64) -> f64 {
+        value / self.micros()
+    }
+
+    /// Formats a value in microseconds in this unit, e.g. `11.2ms`.
+    pub fn format(self, value: f64) -> String {
+        format!("{}{}", round(self.convert(value), 3), self.symbol())
+    }
+
+    /// Describes a latency axis in this unit, e.g. `Milliseconds`.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Us => "Microseconds",
+            Self::Ms => "Milliseconds",
+            Self::S => "Seconds",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Us => "us",
+            Self::Ms => "ms",
+            Self::S => "s",
+        }
+    }
+
+    fn micros(self) -> f64 {
+        match self {
+            Self::Us => 1.0,
+            Self::Ms => MICROS_PER_MILLI,
+            Self::S => 1_000_000.0,
+        }
+    }
+}
+
+/// Parses a duration such as `50ms`, `250us` or `1.5s` into microseconds, the
+/// unit histogram values are recorded in. A bare number is taken as
+/// microseconds.
+pub fn parse_duration(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration `{}`", s))?;
+
+    let scale = match unit.trim() {
+        "" => 1.0,
+        unit => {
+            parse_unit(unit).map_err(|_| format!("Unknown duration unit `{}` in `{}`", unit, s))?
+        }
+    };
+
+    Ok(value * scale)
+}
+
+/// Parses a duration like `parse_duration`, rejecting zero.
+pub fn parse_positive_duration(s: &str) -> Result<f64, String> {
+    match parse_duration(s)? {
+        value if value > 0.0 => Ok(value),
+        _ => Err(format!("Duration `{}` must be greater than zero", s.trim())),
+    }
+}
+
+/// Parses a unit of time, `ns`, `us`, `ms` or `s`, into the number of
+/// microseconds it represents.
+pub fn parse_unit(s: &str) -> Result<f64, String> {
+    match s.trim() {
+        "ns" => Ok(0.001),
+        "us" | "µs" => Ok(1.0),
+        "ms" => Ok(MICROS_PER_MILLI),
+        "s" => Ok(1_000_000.0),
+        unit => Err(format!("Unknown unit `{}`, expected ns, us, ms or s", unit)),
+    }
+}
+
+/// Parses a percentile (0.0 - 1.0), see `check_percentile`.
+pub fn parse_percentile(s: &str) -> Result<f64, String> {
+    let percentile: f64 = s
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid percentile `{}`", s))?;
+
+    check_percentile(percentile)
+}
+
+/// Rejects percentiles outside 0 - 1, and 1 itself, which the line chart's
+/// percentile axis ends short of as it would plot it at infinity.
+pub fn check_percentile(percentile: f64) -> Result<f64, String> {
+    if !(0.0..1.0).contains(&percentile) {
+        return Err(format!(
+            "Percentile `{}` must be at least 0 and less than 1",
+            percentile
+        ));
+    }
+
+    Ok(percentile)
+}
+
+/// Formats a percentile (0.0 - 1.0) as e.g. `p99.9`.
+pub fn format_percentile(percentile: f64) -> String {
+    format!("p{}", round(percentile * 100.0, 6))
+}
+
+/// Formats a value in microseconds as milliseconds, e.g. `11.2ms`.
+pub fn format_millis(value: f64) -> String {
+    LatencyUnit::Ms.format(value)
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let scale = 10f64.powi(decimals);
+    (value * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("50ms"), Ok(50_000.0));
+        assert_eq!(parse_duration("250us"), Ok(250.0));
+        assert_eq!(parse_duration("1.5s"), Ok(1_500_000.0));
+        assert_eq!(parse_duration("500ns"), Ok(0.5));
+        assert_eq!(parse_duration("83"), Ok(83.0));
+        assert!(parse_duration("5 parsecs").is_err());
+        assert!(parse_duration("ms").is_err());
+        assert_eq!(parse_positive_duration("100us"), Ok(100.0));
+        assert!(parse_positive_duration("0ms").is_err());
+    }
+
+    #[test]
+    fn test_parse_percentile() {
+        assert_eq!(parse_percentile("0.999"), Ok(0.999));
+        assert_eq!(parse_percentile("0"), Ok(0.0));
+        assert!(parse_percentile("1").is_err());
+        assert!(parse_percentile("5").is_err());
+        assert!(parse_percentile("-0.1").is_err());
+        assert!(parse_percentile("p99").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_percentile(0.999), "p99.9");
+        assert_eq!(format_percentile(0.5), "p50");
+        assert_eq!(format_millis(11_234.5), "11.235ms");
+        assert_eq!(format_millis(200_000.0), "200ms");
+        assert_eq!(LatencyUnit::Us.format(11_234.5), "11234.5us");
+        assert_eq!(LatencyUnit::S.format(1_500_000.0), "1.5s");
+        assert_eq!(LatencyUnit::S.convert(250_000.0), 0.25);
+    }
+}
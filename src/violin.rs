@@ -1,128 +1,400 @@
-use csaps::CubicSmoothingSpline;
 use itertools::Itertools;
+use plotters::coord::ranged1d::{AsRangedCoord, ValueFormatter};
+use plotters::coord::Shift;
 use plotters::prelude::*;
 
-use crate::hgrm::HGRMs;
+use crate::density::density;
+use crate::hgrm::{HGRMs, HGRM};
+use crate::renderer::{self, Chart, Facet, RenderOptions, Style};
 
-const DARK_BLUE: RGBColor = RGBColor(31, 120, 180);
+const PANEL_WIDTH: u32 = 320;
 
-pub struct Violin<'a> {
-    histograms: HGRMs,
-    filename: &'a str,
+/// Which part of a row a violin is drawn in, split violins place one
+/// histogram above the axis and another below it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Side {
+    Both,
+    Upper,
+    Lower,
 }
 
-impl<'a> Violin<'a> {
-    pub fn new(histograms: HGRMs, filename: &'a str) -> Self {
-        Self {
-            histograms,
-            filename,
+impl Side {
+    /// Offset of markers from the axis, towards the side's half.
+    fn offset(&self) -> f64 {
+        match self {
+            Side::Both => 0.0,
+            Side::Upper => 0.12,
+            Side::Lower => -0.12,
         }
     }
+}
 
-    pub fn render(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let histograms = &self.histograms;
-        let filen = self.filename;
-        let x_range = 0.0..(histograms.max_latency() / 1000f64);
-        let y_range = -0.5..histograms.len() as f64 - 0.5;
+pub struct Violin {
+    histograms: HGRMs,
+    facets: Vec<Facet>,
+    options: RenderOptions,
+}
 
-        let size = (960, 300 + (18 * histograms.len() as u32));
-        let root = SVGBackend::new(filen, size).into_drawing_area();
+impl Violin {
+    pub fn new(histograms: HGRMs, options: RenderOptions) -> Self {
+        let facets = options.facets(&histograms);
 
-        let mut chart = ChartBuilder::on(&root)
-            .caption("Latency", ("sans-serif", 30).into_font())
-            .margin((5).percent())
-            .set_label_area_size(LabelAreaPosition::Left, (10).percent_width().min(60))
-            .set_label_area_size(LabelAreaPosition::Bottom, (5).percent_width().min(40))
-            .build_cartesian_2d(x_range, y_range)?;
+        Self {
+            histograms,
+            facets,
+            options,
+        }
+    }
 
-        let y_label_formatter = |v: &f64| {
-            let histogram = &histograms[v.round() as usize];
+    /// Violins drawn on each row of a panel, pairs of histograms share a row
+    /// when split.
+    fn rows<'h>(&self, histograms: &'h HGRMs) -> Vec<Vec<(usize, &'h HGRM, Side)>> {
+        if self.options.split {
+            histograms
+                .iter()
+                .enumerate()
+                .chunks(2)
+                .into_iter()
+                .map(|pair| {
+                    pair.zip([Side::Upper, Side::Lower])
+                        .map(|((idx, histogram), side)| (idx, histogram, side))
+                        .collect()
+                })
+                .collect()
+        } else {
+            histograms
+                .iter()
+                .enumerate()
+                .map(|(idx, histogram)| vec![(idx, histogram, Side::Both)])
+                .collect()
+        }
+    }
 
-            match &histogram.name {
+    fn row_label(&self, row: &[(usize, &HGRM, Side)]) -> String {
+        match row {
+            [(_, histogram, Side::Both)] => match self.options.series_label(histogram) {
                 Some(filename) => {
                     format!("{}, {} Total", filename, histogram.total_count)
                 }
                 None => {
                     format!("{} Total", histogram.total_count)
                 }
-            }
-        };
+            },
+            _ => row
+                .iter()
+                .map(|(idx, histogram, _)| self.options.series_name(*idx, histogram))
+                .join(" / "),
+        }
+    }
+
+    fn draw_violins<DB, X>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &Style,
+        facet: &Facet,
+        x_spec: X,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+        X: AsRangedCoord<Value = f64>,
+        X::CoordDescType: ValueFormatter<f64>,
+    {
+        let histograms = &facet.histograms;
+        let rows = self.rows(histograms);
+        let slos = &self.options.slos;
+        let unit = self.options.latency_unit;
+        let theme = style.theme;
+        let highlight = theme.highlight();
+        let y_range = -0.5..rows.len() as f64 - 0.5;
+        let (y_min, y_max) = (y_range.start, y_range.end);
+
+        let labels: Vec<String> = rows.iter().map(|row| self.row_label(row)).collect();
+        let y_label_formatter = |v: &f64| labels[v.round() as usize].clone();
+
+        // Leave room for the longest series label, up to 40% of the panel
+        let (width, _) = root.dim_in_pixel();
+        let label_len = labels.iter().map(String::len).max().unwrap_or(0);
+        let label_area = (style.font_size(12.0) * 0.65 * label_len as f64) as u32 + style.px(40);
+
+        let mut builder = ChartBuilder::on(root);
+        if let Some(name) = &facet.name {
+            builder.caption(name, style.font(16.0));
+        }
+
+        let mut chart = builder
+            .margin((5).percent())
+            .set_label_area_size(LabelAreaPosition::Left, label_area.min(width * 2 / 5))
+            .set_label_area_size(
+                LabelAreaPosition::Bottom,
+                (5).percent_width().min(style.px(40) as i32),
+            )
+            .build_cartesian_2d(x_spec, y_range)?;
+
+        let x_range = chart.x_range();
+        let (x_min, x_max) = (x_range.start, x_range.end);
 
         chart
             .configure_mesh()
             .disable_mesh()
-            .y_desc("Input")
-            .x_desc("Latency (ms)")
+            .y_desc(self.options.y_label.as_deref().unwrap_or("Input"))
+            .x_desc(
+                self.options
+                    .x_label
+                    .clone()
+                    .unwrap_or_else(|| format!("Latency ({})", unit.symbol())),
+            )
             .y_label_formatter(&y_label_formatter)
-            .y_labels(histograms.len())
-            .x_label_formatter(&|v: &f64| (v.round() as usize).to_string())
+            .y_labels(rows.len())
+            .axis_style(theme.axis())
+            .label_style(style.font(12.0))
+            .axis_desc_style(style.font(14.0))
             .draw()?;
 
-        for (idx, histogram) in histograms.iter().enumerate() {
-            let base = idx as f64;
+        for slo in slos {
+            chart.draw_series(std::iter::once(Rectangle::new(
+                [(slo.threshold_in(unit), y_min), (x_max, y_max)],
+                highlight.mix(0.1).filled(),
+            )))?;
+        }
 
-            let histogram_max_y = histogram
-                .percentiles
-                .iter()
-                .scan(0f64, |prev, percentile| {
-                    let count_diff = (percentile.total_count as f64) - *prev;
-                    *prev = percentile.total_count as f64;
+        let legend_width = style.px(20) as i32;
+        let legend = move |color: RGBColor| {
+            move |(x, y): (i32, i32)| {
+                Rectangle::new(
+                    [
+                        (x, y - legend_width / 4),
+                        (x + legend_width, y + legend_width / 4),
+                    ],
+                    color.filled(),
+                )
+            }
+        };
 
-                    Some(count_diff)
-                })
-                .fold(f64::MIN, |a, b| a.max(b));
+        for (base, row) in rows.iter().enumerate() {
+            let base = base as f64;
 
-            let scaler = |i: f64| (i / histogram_max_y) * 0.9;
+            for (idx, histogram, side) in row {
+                let color = self.options.series_color(*idx, histogram);
+                let smoothdata: Vec<(f64, f64)> = density(
+                    histogram,
+                    self.options.smoothing,
+                    self.options.smoothing_factor,
+                    self.options.bandwidth,
+                    self.options.clamp.unwrap_or(true),
+                )?
+                .into_iter()
+                .map(|(x, y)| (unit.convert(x), y))
+                .filter(|(x, _)| *x >= x_min)
+                .collect();
 
-            let mut data: Vec<(f64, f64)> = histogram
-                .percentiles
-                .iter()
-                .scan(0f64, |prev, percentile| {
-                    let count_diff = (percentile.total_count as f64) - *prev;
-                    *prev = percentile.total_count as f64;
+                if *side != Side::Lower {
+                    let series = chart.draw_series(AreaSeries::new(
+                        smoothdata.iter().map(|(x, y)| (*x, base + *y / 2.0)),
+                        base,
+                        color,
+                    ))?;
 
-                    Some((percentile.value / 1000f64, count_diff))
-                })
-                .group_by(|(x, _y)| *x)
-                .into_iter()
-                .map(|(x, ys)| {
-                    let s = ys.into_iter().map(|(_, ys)| ys).sum();
+                    if *side == Side::Upper {
+                        series
+                            .label(self.options.series_name(*idx, histogram))
+                            .legend(legend(color));
+                    }
+                }
 
-                    (x, scaler(s))
-                })
-                .collect();
+                if *side != Side::Upper {
+                    let series = chart.draw_series(AreaSeries::new(
+                        smoothdata.iter().map(|(x, y)| (*x, base - *y / 2.0)),
+                        base,
+                        color,
+                    ))?;
+
+                    if *side == Side::Lower {
+                        series
+                            .label(self.options.series_name(*idx, histogram))
+                            .legend(legend(color));
+                    }
+                }
+
+                let center = base + side.offset();
+                let value =
+                    |percentile: f64| unit.convert(histogram.value_at_percentile(percentile));
+                let marker = theme.text();
+
+                // Interquartile range, median and p99, as on a box plot
+                chart.draw_series(std::iter::once(Rectangle::new(
+                    [(value(0.25), center - 0.03), (value(0.75), center + 0.03)],
+                    marker.filled(),
+                )))?;
+                chart.draw_series(std::iter::once(PathElement::new(
+                    vec![(value(0.99), center - 0.1), (value(0.99), center + 0.1)],
+                    marker.stroke_width(style.px(2)),
+                )))?;
+                chart.draw_series(std::iter::once(Circle::new(
+                    (value(0.5), center),
+                    style.px(4),
+                    theme.background().filled(),
+                )))?;
+                chart.draw_series(std::iter::once(Circle::new(
+                    (value(0.5), center),
+                    style.px(4),
+                    marker.stroke_width(style.px(1)),
+                )))?;
+
+                chart.draw_series(slos.iter().map(|slo| {
+                    let marker = if slo.is_violated_by(histogram) {
+                        highlight.stroke_width(style.px(2))
+                    } else {
+                        theme.text().stroke_width(style.px(2))
+                    };
+
+                    Cross::new((value(slo.percentile), center), style.px(5), marker)
+                }))?;
+            }
+        }
+
+        for slo in slos {
+            let threshold = slo.threshold_in(unit);
+
+            chart
+                .draw_series(std::iter::once(PathElement::new(
+                    vec![(threshold, y_min), (threshold, y_max)],
+                    highlight.mix(0.6),
+                )))?
+                .label(slo.legend(histograms, |idx, histogram| {
+                    self.options.series_name(idx, histogram)
+                }))
+                .legend(move |(x, y)| {
+                    PathElement::new(vec![(x, y), (x + legend_width, y)], highlight)
+                });
+        }
+
+        if !slos.is_empty() || self.options.split {
+            chart
+                .configure_series_labels()
+                .background_style(theme.legend_background())
+                .border_style(theme.legend_border())
+                .label_font(style.font(12.0))
+                .draw()?;
+        }
+
+        Ok(())
+    }
+}
 
-            data.sort_by(|(x1, _), (x2, _)| x1.partial_cmp(x2).unwrap());
+impl Chart for Violin {
+    fn options(&self) -> &RenderOptions {
+        &self.options
+    }
 
-            let xs: Vec<f64> = data.iter().map(|(x, _y)| *x).collect();
-            let ys: Vec<f64> = data.iter().map(|(_x, y)| *y).collect();
+    fn size(&self) -> (u32, u32) {
+        let (rows, columns) = self.options.grid(self.facets.len());
+        let panel_len = self
+            .facets
+            .iter()
+            .map(|facet| self.rows(&facet.histograms).len() as u32)
+            .max()
+            .unwrap_or(0);
+        let height = if rows == 1 {
+            300 + 18 * panel_len
+        } else {
+            60 + (240 + 18 * panel_len) * rows as u32
+        };
 
-            let smooth_ys = CubicSmoothingSpline::new(&xs, &ys)
-                .with_smooth(0.99)
-                .make()
-                .unwrap()
-                .evaluate(&xs)
-                .unwrap();
+        (
+            self.options
+                .width
+                .unwrap_or_else(|| (PANEL_WIDTH * columns as u32).max(960)),
+            self.options.height.unwrap_or(height),
+        )
+    }
 
-            let smoothdata: Vec<(f64, f64)> = xs
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &Style,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        let unit = self.options.latency_unit;
+        let x_max = match self.options.y_max {
+            Some(y_max) => unit.convert(y_max),
+            None => self
+                .options
+                .slos
                 .iter()
-                .zip(smooth_ys.iter())
-                .map(|(x, y)| (*x, *y))
-                .collect();
+                .fold(unit.convert(self.histograms.max_latency()), |a, slo| {
+                    a.max(slo.threshold_in(unit))
+                }),
+        };
+
+        root.fill(&style.theme.background())?;
+        let root = renderer::draw_titles(root, &self.options, style)?;
+        let areas = renderer::facet_areas(&root, &self.options, self.facets.len());
+
+        for (area, facet) in areas.iter().zip(&self.facets) {
+            if self.options.log_y {
+                let x_min = match self.options.y_min {
+                    Some(y_min) => unit.convert(y_min),
+                    None => unit.convert(self.histograms.min_latency()),
+                };
+
+                self.draw_violins(
+                    area,
+                    style,
+                    facet,
+                    (x_min.max(f64::EPSILON)..x_max).log_scale(),
+                )?;
+            } else {
+                let x_min = unit.convert(self.options.y_min.unwrap_or(0.0));
 
-            chart.draw_series(AreaSeries::new(
-                smoothdata.iter().map(|(x, y)| (*x, base + *y / 2.0)),
-                base,
-                &DARK_BLUE, // Palette99::pick(idx),
-            ))?;
-
-            chart.draw_series(AreaSeries::new(
-                smoothdata.iter().map(|(x, y)| (*x, base - *y / 2.0)),
-                base,
-                &DARK_BLUE, // Palette99::pick(idx),
-            ))?;
+                self.draw_violins(area, style, facet, x_min..x_max)?;
+            }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_split_rows() {
+        let histograms = HGRMs::new(vec![
+            HGRM::new().set_name(Some("a")),
+            HGRM::new().set_name(Some("b")),
+            HGRM::new().set_name(Some("c")),
+        ]);
+        let options = RenderOptions {
+            split: true,
+            ..Default::default()
+        };
+        let violin = Violin::new(histograms, options);
+
+        let rows: Vec<(String, Vec<Side>)> = violin
+            .rows(&violin.histograms)
+            .iter()
+            .map(|row| {
+                (
+                    violin.row_label(row),
+                    row.iter().map(|(_, _, side)| *side).collect(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                ("a / b".to_string(), vec![Side::Upper, Side::Lower]),
+                ("c".to_string(), vec![Side::Upper]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Re-renders charts whenever their inputs change.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode};
+
+/// Changes within this long of each other trigger a single re-render.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Absolute path to `path`, resolving symlinks in its parent when it doesn't
+/// exist yet, e.g. an output which hasn't been written.
+fn resolve(path: &str) -> PathBuf {
+    let path = Path::new(path);
+
+    path.canonicalize().unwrap_or_else(|_| {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        match (parent.canonicalize(), path.file_name()) {
+            (Ok(parent), Some(name)) => parent.join(name),
+            _ => path.to_path_buf(),
+        }
+    })
+}
+
+/// Whether a change to `path` should trigger a re-render: it's one of the
+/// `inputs` or directly inside an input directory, as directories are loaded
+/// without their subdirectories, and isn't one of the `outputs`.
+fn is_relevant(path: &Path, inputs: &[PathBuf], outputs: &[PathBuf]) -> bool {
+    !outputs.iter().any(|output| output == path)
+        && inputs
+            .iter()
+            .any(|input| path == input || path.parent() == Some(input))
+}
+
+/// Runs `render` once, then again each time one of the `inputs` changes,
+/// until interrupted. Errors are logged rather than ending the watch. Changes
+/// to `outputs` are ignored, so charts written next to their inputs don't
+/// re-render themselves.
+pub fn watch<F>(
+    inputs: &[String],
+    outputs: &[&str],
+    mut render: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Result<(), Box<dyn std::error::Error>>,
+{
+    let inputs = inputs
+        .iter()
+        .map(|input| {
+            Path::new(input)
+                .canonicalize()
+                .map_err(|e| format!("Unable to watch {}: {}", input, e))
+        })
+        .collect::<Result<Vec<PathBuf>, String>>()?;
+    let outputs: Vec<PathBuf> = outputs.iter().map(|output| resolve(output)).collect();
+
+    // Files are watched through their directory, so they're still followed
+    // when replaced rather than written to
+    let watched: BTreeSet<PathBuf> = inputs
+        .iter()
+        .map(|input| match input.parent() {
+            Some(parent) if input.is_file() => parent.to_path_buf(),
+            _ => input.clone(),
+        })
+        .collect();
+
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE, tx)?;
+
+    for path in &watched {
+        debouncer
+            .watcher()
+            .watch(path, RecursiveMode::NonRecursive)?;
+    }
+
+    if let Err(e) = render() {
+        eprintln!("{}", e);
+    }
+
+    eprintln!("Watching for changes, press Ctrl-C to stop...");
+
+    for result in rx {
+        match result {
+            Ok(events)
+                if events
+                    .iter()
+                    .any(|event| is_relevant(&event.path, &inputs, &outputs)) =>
+            {
+                eprintln!("Change detected, re-rendering...");
+
+                if let Err(e) = render() {
+                    eprintln!("{}", e);
+                }
+            }
+            Ok(_) => (),
+            Err(e) => eprintln!("Watch error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_relevant() {
+        let inputs = vec![PathBuf::from("/runs"), PathBuf::from("/tmp/a.hgrm")];
+        let outputs = vec![PathBuf::from("/runs/chart.svg")];
+
+        assert!(is_relevant(Path::new("/tmp/a.hgrm"), &inputs, &outputs));
+        assert!(is_relevant(Path::new("/runs/b.hgrm"), &inputs, &outputs));
+        assert!(!is_relevant(Path::new("/runs/1/b.hgrm"), &inputs, &outputs));
+        assert!(!is_relevant(Path::new("/tmp/b.hgrm"), &inputs, &outputs));
+        assert!(!is_relevant(
+            Path::new("/runs/chart.svg"),
+            &inputs,
+            &outputs
+        ));
+    }
+}
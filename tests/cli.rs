@@ -0,0 +1,76 @@
+use std::process::Command;
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+/// Runs the binary with `args`, returning its stdout.
+fn hdr2plot(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_hdr2plot"))
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Cells of the row of the compare table starting with `percentile`.
+fn row(summary: &str, percentile: &str) -> Vec<String> {
+    let line = summary
+        .lines()
+        .find(|line| line.starts_with(&format!("{} ", percentile)))
+        .unwrap();
+
+    line.split("  ")
+        .map(str::trim)
+        .filter(|cell| !cell.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_compare_fixtures() {
+    let fixtures = ["test/data/12Krps.hgrm", "test/data/16Krps.hgrm"];
+
+    let summary = hdr2plot(&["compare", fixtures[0], fixtures[1]]);
+    let p50 = row(&summary, "p50");
+    assert_eq!(p50[1], "4.917ms (4.915ms to 4.919ms)");
+    assert_eq!(p50[2], "8.07ms (8.068ms to 8.073ms)");
+    assert!(row(&summary, "p99")[1].starts_with("9.071ms"));
+
+    let summary = hdr2plot(&["compare", fixtures[0], fixtures[1], "--latency-unit", "us"]);
+    assert!(row(&summary, "p50")[1].starts_with("4917.386us"));
+}
+
+#[test]
+fn test_validate_truncated() {
+    let truncated: String = std::fs::read_to_string("test/data/16Krps.hgrm")
+        .unwrap()
+        .split_inclusive('\n')
+        .take(40)
+        .collect();
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        file.path(),
+        std::fs::read_to_string("test/data/12Krps.hgrm").unwrap() + &truncated,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_hdr2plot"))
+        .args([
+            "validate",
+            "--input-format",
+            "hgrm",
+            file.path().to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("at line 112, column 8"));
+}